@@ -0,0 +1,103 @@
+use std::path::Path;
+
+pub struct Completion {
+    base: String,
+    candidates: Vec<String>,
+    index: usize,
+}
+
+impl Completion {
+    // Completes the last word of `line`. The first word is a command name, and the
+    // arguments of `path_commands` are file paths.
+    pub fn new(line: &str, commands: &[&str], path_commands: &[&str]) -> Option<Completion> {
+        let line = line.trim_start();
+        let (base, candidates) = match line.split_once(char::is_whitespace) {
+            None => (String::new(), complete_command(line, commands)),
+            Some((command, argument)) if path_commands.contains(&command) => {
+                let argument = argument.trim_start();
+                (line[..line.len() - argument.len()].to_owned(), complete_path(argument))
+            }
+            Some(_) => return None,
+        };
+        (!candidates.is_empty()).then_some(Completion { base, candidates, index: 0 })
+    }
+
+    pub fn current(&self) -> String {
+        format!("{}{}", self.base, self.candidates[self.index])
+    }
+
+    pub fn advance(&mut self) {
+        self.index = crate::util::rotate_forward(0, self.candidates.len(), self.index);
+    }
+}
+
+fn complete_command(prefix: &str, commands: &[&str]) -> Vec<String> {
+    let mut candidates: Vec<String> = commands
+        .iter()
+        .filter(|command| command.starts_with(prefix))
+        .map(|&command| command.to_owned())
+        .collect();
+    candidates.sort();
+    candidates
+}
+
+fn complete_path(prefix: &str) -> Vec<String> {
+    let (directory, name) = match prefix.rfind('/') {
+        Some(index) => prefix.split_at(index + 1),
+        None => ("", prefix),
+    };
+    let Ok(entries) = Path::new(if directory.is_empty() { "." } else { directory }).read_dir()
+    else {
+        return Vec::new();
+    };
+    let mut candidates: Vec<String> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let mut entry_name = entry.file_name().into_string().ok()?;
+            if !entry_name.starts_with(name) || (entry_name.starts_with('.') && name.is_empty()) {
+                return None;
+            }
+            if entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
+                entry_name.push('/');
+            }
+            Some(format!("{directory}{entry_name}"))
+        })
+        .collect();
+    candidates.sort();
+    candidates
+}
+
+#[cfg(test)]
+mod tests {
+    const COMMANDS: &[&str] = &["edit", "quit", "tabnext", "tabopen"];
+
+    fn candidates(line: &str) -> Vec<String> {
+        let Some(mut completion) = super::Completion::new(line, COMMANDS, &["edit"])
+        else {
+            return Vec::new();
+        };
+        let mut candidates = vec![completion.current()];
+        for _ in 1..completion.candidates.len() {
+            completion.advance();
+            candidates.push(completion.current());
+        }
+        completion.advance();
+        assert_eq!(completion.current(), candidates[0]);
+        candidates
+    }
+
+    #[test]
+    fn complete_command() {
+        assert_eq!(candidates("q"), ["quit"]);
+        assert_eq!(candidates("tab"), ["tabnext", "tabopen"]);
+        assert_eq!(candidates("x"), Vec::<String>::new());
+        assert_eq!(candidates("quit a"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn complete_path() {
+        assert_eq!(candidates("edit src/mai"), ["edit src/main.rs"]);
+        assert_eq!(candidates("edit  sr"), ["edit  src/"]);
+        assert_eq!(candidates("edit src/nonexistent"), Vec::<String>::new());
+    }
+}
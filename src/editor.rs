@@ -1,23 +1,75 @@
-use crate::indexvec::IndexVec;
+use crate::indexvec::{IndexVec, VecIndex};
 use crate::terminal::{Position, Size};
-use crate::{settings, text, util};
+use crate::{search, settings, text, textobject, util};
+use std::collections::HashMap;
 use std::io;
+use std::ops::Range;
 use std::path::PathBuf;
-use std::time::SystemTime;
+use std::time::{Duration, Instant, SystemTime};
 
-crate::define_index!(pub BufferID);
+crate::define_index!(pub BufferID, generational);
 crate::define_index!(pub WindowID);
 
 pub type BufferVec = IndexVec<Buffer, BufferID>;
 pub type WindowVec = IndexVec<Window, WindowID>;
 
+const SCROLL_LINES: u16 = 3;
+const JUMP_LIST_CAPACITY: usize = 100;
+const MESSAGE_TIMEOUT: Duration = Duration::from_secs(5);
+const FILE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+const SWAP_INTERVAL: Duration = Duration::from_secs(4);
+pub const MINIMUM_SIZE: Size = Size { width: 12, height: 4 };
+
 #[derive(Clone, Copy, PartialEq, Default, Debug)]
 pub enum Mode {
     #[default]
     Normal,
     Insert,
+    Replace,
     Window,
     CommandLine,
+    VisualBlock,
+}
+
+// A change that can be repeated with `.`.
+#[derive(Clone, Debug)]
+pub enum Change {
+    DeleteChars(usize),
+    DeleteLines(usize),
+    JoinLines(usize),
+    Object(Operator, char, bool),
+    Motion(Operator, char, usize),
+    EmptyLines(usize),
+    ShiftLines(usize, bool),
+    ToggleCase(usize),
+    ReplaceChars(char, usize),
+    Paste(char, usize, bool),
+    Insert(String),
+    // A change that entered insert mode, like `cw`, and the text inserted after it.
+    InsertAfter(Box<Change>, String),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Operator {
+    Delete,
+    Change,
+    Yank,
+}
+
+// How `:sort` orders lines. Numeric sorting orders lines by the first number in them, and puts
+// the lines without numbers first.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Sort {
+    pub reverse: bool,
+    pub unique: bool,
+    pub numeric: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
+pub enum LineEnding {
+    #[default]
+    Unix,
+    Dos,
 }
 
 pub struct FileInfo {
@@ -31,12 +83,37 @@ pub struct Buffer {
     pub file_info: Option<FileInfo>,
     pub settings: settings::BufferSettings,
     pub windows: Vec<WindowID>,
+    pub cursor: Position,
+    // Whether invalid UTF-8 was replaced when the file was read.
+    pub converted: bool,
+    pub line_ending: LineEnding,
+    // Whether the file did not end with a line break when it was last read or written.
+    pub no_end_of_line: bool,
+    // Whether the text has been changed since it was last read or written.
+    pub modified: bool,
+    // Whether the text has been changed since it was last written to the swap file.
+    pub swap_pending: bool,
+    // Whether this buffer has written a swap file that has not been removed.
+    pub swap_written: bool,
+    // Scratch buffers, like the help, are read-only buffers without a file that are closed with
+    // `q`. Their name is shown in the status line.
+    pub scratch: Option<&'static str>,
+    // The directory listed by an "Explore" scratch buffer, which has a line for each entry.
+    pub directory: Option<PathBuf>,
+}
+
+// The text of a yank or a deletion. Linewise text is made of whole lines, and does not include
+// the line break after the last one.
+#[derive(Clone, Default, Debug)]
+pub struct Register {
+    pub text: String,
+    pub linewise: bool,
 }
 
+// The buffer shown in a window. The scroll position is kept by the window, so windows that show
+// the same buffer scroll independently, and resizing a window keeps its view.
 #[derive(Clone, Copy, Debug)]
 pub struct View {
-    pub offset: u16,
-    pub size: Size,
     pub buffer: BufferID,
 }
 
@@ -46,9 +123,31 @@ pub struct Window {
     pub cursor: Position,
     pub size: Size,
     pub view: Option<View>,
+    // The first screen column that is shown, with tabs expanded, and the first line that is shown.
+    pub scroll_x: u16,
+    pub scroll_y: u16,
     pub settings: settings::WindowSettings,
     pub is_open: bool,
     pub redraw: bool,
+    // The screen column that vertical motions keep, and where the last one left the cursor. The
+    // column only applies while the cursor has not been moved elsewhere.
+    pub sticky_column: Option<(usize, Position)>,
+}
+
+// A motion to a character on the cursor line: `f` and `t` forward, `F` and `T` backward. The
+// `t` motions stop just before the character.
+#[derive(Clone, Copy, Debug)]
+pub struct FindChar {
+    pub character: char,
+    pub backward: bool,
+    pub till: bool,
+}
+
+// A search that is being typed, and where the cursor was when it began.
+pub struct SearchPreview {
+    pub pattern: String,
+    pub cursor: Position,
+    pub scroll: (u16, u16),
 }
 
 pub struct Tab {
@@ -61,27 +160,204 @@ pub struct Editor {
     pub windows: WindowVec,
     pub tabs: Vec<Tab>,
     pub status: Option<String>,
+    pub status_shown: bool,
+    pub status_time: Instant,
+    pub last_file_check: Instant,
+    pub last_swap_write: Instant,
     pub mode: Mode,
     pub size: Size,
     pub settings: settings::EditorSettings,
     pub current_tab: usize,
+    pub overwritten: Vec<Option<char>>,
+    pub insertion: String,
+    // The change that entered insert mode, repeated together with the inserted text.
+    pub insert_change: Option<Change>,
+    // The other corner of the block selected in visual block mode.
+    pub visual_anchor: Position,
+    // The lines left to insert on after a block insert, the screen column, and whether the
+    // text is appended after the block.
+    pub block_insert: Option<(Range<usize>, usize, bool)>,
+    // Whether edits are undone together with the previous ones. An insert or replace mode session
+    // is undone as one change, from its first edit until the mode is left with Esc or the cursor
+    // jumps with a mouse click.
+    pub undo_group: bool,
+    pub last_change: Option<Change>,
+    pub search: Option<String>,
+    pub search_backward: bool,
+    // Whether the matches of the last search are highlighted, until `:nohlsearch`.
+    pub search_highlight: bool,
+    pub search_preview: Option<SearchPreview>,
+    pub marks: HashMap<char, (BufferID, Position)>,
+    pub jumps: Vec<(BufferID, Position)>,
+    // The position in the jump list, which is its length unless jumping back.
+    pub jump_index: usize,
+    pub last_find: Option<FindChar>,
+    // The registers by name. The unnamed register `"` holds the last yank or deletion.
+    pub registers: HashMap<char, Register>,
+    // The register that yanks, deletions, and pastes use, selected with `"` before the command.
+    // An uppercase name appends to the register of the lowercase name.
+    pub register: char,
 }
 
 impl FileInfo {
     pub fn new(path: PathBuf) -> std::io::Result<FileInfo> {
         std::fs::metadata(&path)?.modified().map(|time| FileInfo { path, time })
     }
+    pub fn name(&self) -> String {
+        self.path.file_name().unwrap_or(self.path.as_os_str()).to_string_lossy().into_owned()
+    }
+    // The swap file is kept next to the file, so that it can be found again after a crash.
+    pub fn swap_path(&self) -> PathBuf {
+        self.path.with_file_name(format!(".{}.tek.swp", self.name()))
+    }
+    pub fn file_type(&self) -> Option<&'static str> {
+        match self.path.extension()?.to_str()? {
+            "rs" => Some("rust"),
+            "toml" => Some("toml"),
+            "md" => Some("markdown"),
+            "c" | "h" => Some("c"),
+            "cpp" | "hpp" | "cc" | "cxx" => Some("cpp"),
+            "py" => Some("python"),
+            "sh" => Some("sh"),
+            "json" => Some("json"),
+            "txt" => Some("text"),
+            _ => None,
+        }
+    }
+}
+
+impl LineEnding {
+    // Text with mixed line endings is read as it is, so that writing it back changes nothing.
+    pub fn detect(text: &str) -> LineEnding {
+        let crlf = text.matches("\r\n").count();
+        if crlf != 0 && crlf == text.matches('\n').count() {
+            LineEnding::Dos
+        }
+        else {
+            LineEnding::Unix
+        }
+    }
+    pub fn name(self) -> &'static str {
+        match self {
+            LineEnding::Unix => "unix",
+            LineEnding::Dos => "dos",
+        }
+    }
 }
 
 impl Buffer {
     pub fn read(path: std::path::PathBuf) -> std::io::Result<Buffer> {
+        let file = std::fs::File::open(&path)?;
+        let size = file.metadata()?.len() as usize;
+        let (mut text, converted) = text::read_lossy(file, size)?;
+        let line_ending = LineEnding::detect(&text);
+        if line_ending == LineEnding::Dos {
+            text = text.replace("\r\n", "\n");
+        }
+        let no_end_of_line = !text.is_empty() && !text.ends_with('\n');
         Ok(Buffer {
-            text: std::fs::read_to_string(&path)?.into(),
+            text: text.into(),
             file_info: Some(FileInfo::new(path)?),
             settings: settings::BufferSettings::default(),
             windows: Vec::new(),
+            cursor: Position::default(),
+            converted,
+            line_ending,
+            no_end_of_line,
+            modified: false,
+            swap_pending: false,
+            swap_written: false,
+            scratch: None,
+            directory: None,
         })
     }
+
+    // The number of lines, where a final line break does not begin another line.
+    pub fn lines(&self) -> usize {
+        let last_line = self.text.line_count() - 1;
+        last_line + usize::from(self.text.line_length(last_line) != 0)
+    }
+
+    // Writes the text to `path`, which becomes the buffer's file. Unless forced, a file that has
+    // changed on disk since it was read is not overwritten.
+    pub fn save(&mut self, path: PathBuf, force: bool) -> io::Result<usize> {
+        let changed = self.file_info.as_ref().is_some_and(|info| {
+            let time = std::fs::metadata(&path).and_then(|metadata| metadata.modified());
+            info.path == path && time.is_ok_and(|time| time > info.time)
+        });
+        if changed && !force {
+            return Err(io::Error::other(
+                "The file has been changed since reading it (add ! to override)",
+            ));
+        }
+        if self.settings.trim_trailing_whitespace {
+            self.trim_trailing_whitespace();
+        }
+        let bytes = self.write(&path)?;
+        self.no_end_of_line = !self.settings.fixendofline && self.missing_end_of_line();
+        self.remove_swap();
+        self.text.take_changed(); // Any trimming was written as well
+        self.modified = false;
+        self.file_info = Some(FileInfo::new(path)?);
+        Ok(bytes)
+    }
+
+    // Removes the spaces and tabs at the end of every line, and returns how many lines had any.
+    // Lines are trimmed from the last one, so the offsets of the lines before stay valid.
+    pub fn trim_trailing_whitespace(&mut self) -> usize {
+        let mut trimmed = 0;
+        for line in (0..self.text.line_count()).rev() {
+            let text = self.text.line(line);
+            let length = text.trim_end_matches([' ', '\t']).len();
+            if length < text.len() {
+                if trimmed == 0 {
+                    self.text.checkpoint();
+                }
+                let start = self.text.byte_offset_of(line, 0);
+                self.text.remove(start + length, text.len() - length);
+                trimmed += 1;
+            }
+        }
+        if trimmed != 0 {
+            (self.modified, self.swap_pending) = (true, true);
+        }
+        trimmed
+    }
+
+    // Writes the text to the swap file, if it has changed since the last time.
+    pub fn write_swap(&mut self) -> io::Result<()> {
+        if let (true, Some(info)) = (self.swap_pending, &self.file_info) {
+            std::fs::write(info.swap_path(), self.text.gather())?;
+            (self.swap_pending, self.swap_written) = (false, true);
+        }
+        Ok(())
+    }
+
+    // Removes the swap file, if this buffer has written one.
+    pub fn remove_swap(&mut self) {
+        if let (true, Some(info)) = (self.swap_written, &self.file_info) {
+            std::fs::remove_file(info.swap_path()).ok();
+        }
+        (self.swap_pending, self.swap_written) = (false, false);
+    }
+
+    fn missing_end_of_line(&self) -> bool {
+        !self.text.is_empty() && self.text.char_at(self.text.len() - 1) != Some('\n')
+    }
+
+    // Writes the text with the line endings it was read with. With `fixendofline`, a line break
+    // is added to text that does not end with one.
+    pub fn write(&self, path: &std::path::Path) -> io::Result<usize> {
+        let mut text = self.text.gather();
+        if self.settings.fixendofline && self.missing_end_of_line() {
+            text.push('\n');
+        }
+        if self.line_ending == LineEnding::Dos {
+            text = text.replace('\n', "\r\n");
+        }
+        std::fs::write(path, &text)?;
+        Ok(text.len())
+    }
 }
 
 impl Window {
@@ -91,14 +367,21 @@ impl Window {
             cursor: Position::default(),
             size,
             view: None,
+            scroll_x: 0,
+            scroll_y: 0,
             settings: settings::WindowSettings::default(),
             is_open: false,
             redraw: true,
+            sticky_column: None,
         }
     }
-    pub fn keep_cursor_within_bounds(&mut self) {
-        self.cursor.x = self.cursor.x.min(self.size.width.saturating_sub(1));
-        self.cursor.y = self.cursor.y.min(self.size.height.saturating_sub(1));
+    pub fn gutter_width(&self, buffer: &Buffer) -> u16 {
+        if self.settings.number {
+            buffer.text.line_count().to_string().len() as u16 + 1
+        }
+        else {
+            0
+        }
     }
     pub fn contains_x(&self, x: u16) -> bool {
         (self.position.x <= x) && (x < self.position.x + self.size.width)
@@ -111,10 +394,46 @@ impl Window {
     }
 }
 
+// The first number in the line, which is negative if it follows a minus sign.
+fn first_number(line: &str) -> Option<i64> {
+    let start = line.find(|character: char| character.is_ascii_digit())?;
+    let end = line[start..].find(|character: char| !character.is_ascii_digit());
+    let number: i64 = line[start..end.map_or(line.len(), |end| start + end)].parse().ok()?;
+    Some(if line[..start].ends_with('-') { -number } else { number })
+}
+
+// The start and length of the window along the horizontal axis, or the vertical one.
+fn window_extent(window: &Window, horizontal: bool) -> (u16, u16) {
+    if horizontal {
+        (window.position.x, window.size.width)
+    }
+    else {
+        (window.position.y, window.size.height)
+    }
+}
+
 impl Editor {
+    // The area available to windows, which is everything above the status line. Windows are
+    // laid out for at least the minimum size, and are not drawn when the terminal is smaller.
+    pub fn layout_size(&self) -> Size {
+        Size {
+            width: self.size.width.max(MINIMUM_SIZE.width),
+            height: self.size.height.max(MINIMUM_SIZE.height) - self.status_rows(),
+        }
+    }
+
+    // The number of rows at the bottom of the screen that are reserved for the status line.
+    pub fn status_rows(&self) -> u16 {
+        u16::from(self.settings.laststatus != 0)
+    }
+
+    pub fn too_small(&self) -> bool {
+        self.size.width < MINIMUM_SIZE.width || self.size.height < MINIMUM_SIZE.height
+    }
+
     pub fn new_tab(&mut self) -> Tab {
         let default_window_id = self.windows.push(Window {
-            size: Size { height: self.size.height - 1, ..self.size },
+            size: self.layout_size(),
             is_open: true,
             redraw: true,
             ..Window::default()
@@ -134,7 +453,28 @@ impl Editor {
             size,
             settings: settings::EditorSettings::default(),
             status: None,
+            status_shown: false,
+            status_time: Instant::now(),
+            last_file_check: Instant::now(),
+            last_swap_write: Instant::now(),
             current_tab: 0,
+            overwritten: Vec::new(),
+            insertion: String::new(),
+            insert_change: None,
+            visual_anchor: Position::default(),
+            block_insert: None,
+            undo_group: false,
+            last_change: None,
+            search: None,
+            search_backward: false,
+            search_highlight: false,
+            search_preview: None,
+            marks: HashMap::new(),
+            jumps: Vec::new(),
+            jump_index: 0,
+            last_find: None,
+            registers: HashMap::new(),
+            register: '"',
         };
         let tab = editor.new_tab();
         editor.tabs.push(tab);
@@ -143,10 +483,131 @@ impl Editor {
 
     pub fn emit_message(&mut self, message: String) {
         self.status = Some(message);
+        self.status_shown = false;
+        self.status_time = Instant::now();
+    }
+
+    // How long until `tick` has work to do, if it ever will without further input.
+    pub fn time_until_tick(&self, now: Instant) -> Option<Duration> {
+        // A message that has not been drawn yet is not cleared, so there is nothing to wait for.
+        let message = self.status.as_ref().filter(|_| self.status_shown);
+        let message = message.map(|_| self.status_time + MESSAGE_TIMEOUT);
+        let file_check =
+            self.settings.autoread.then_some(self.last_file_check + FILE_CHECK_INTERVAL);
+        let swap = self.buffers.indices().any(|id| self.buffers[id].swap_pending);
+        let swap = swap.then_some(self.last_swap_write + SWAP_INTERVAL);
+        let times = message.into_iter().chain(file_check).chain(swap);
+        times.min().map(|time| time.saturating_duration_since(now))
+    }
+
+    // Does the periodic work that is due: clearing stale messages, checking files for changes, and
+    // writing swap files.
+    pub fn tick(&mut self, now: Instant) {
+        if self.status_shown && now >= self.status_time + MESSAGE_TIMEOUT {
+            self.status = None;
+        }
+        if self.settings.autoread && now >= self.last_file_check + FILE_CHECK_INTERVAL {
+            self.last_file_check = now;
+            self.reload_changed_files();
+        }
+        if now >= self.last_swap_write + SWAP_INTERVAL {
+            self.last_swap_write = now;
+            self.write_swap_files();
+        }
+    }
+
+    pub fn write_swap_files(&mut self) {
+        let mut failure = None;
+        for buffer in self.buffers.iter_mut() {
+            if let Err(error) = buffer.write_swap() {
+                let name = buffer.file_info.as_ref().map(FileInfo::name).unwrap_or_default();
+                failure = Some(format!("Unable to write swap file for \"{name}\": {error}"));
+            }
+        }
+        if let Some(message) = failure {
+            self.emit_message(message);
+        }
+    }
+
+    // Swap files are removed when the editor exits normally.
+    pub fn remove_swap_files(&mut self) {
+        self.buffers.iter_mut().for_each(Buffer::remove_swap);
+    }
+
+    // Replaces the text of the focused window's buffer with the text of its swap file.
+    pub fn recover(&mut self) -> io::Result<()> {
+        let Some(view) = self.windows[self.window_focus()].view
+        else {
+            return Err(io::Error::other("No buffer to recover"));
+        };
+        let buffer = &mut self.buffers[view.buffer];
+        let Some(info) = &buffer.file_info
+        else {
+            return Err(io::Error::other("No file name"));
+        };
+        let text = std::fs::read_to_string(info.swap_path())
+            .map_err(|_| io::Error::other(format!("No swap file found for \"{}\"", info.name())))?;
+        buffer.text = text.into();
+        (buffer.modified, buffer.swap_written) = (true, true);
+        for id in self.window_ids().collect::<Vec<_>>() {
+            if self.windows[id].view.is_some_and(|other| other.buffer == view.buffer) {
+                self.clamp_cursor(id);
+            }
+        }
+        self.redraw_buffer(view.buffer);
+        self.emit_message(String::from("Recovery completed, write the buffer to keep it"));
+        Ok(())
+    }
+
+    // Messages disappear on the first key press after they have been drawn.
+    pub fn clear_shown_message(&mut self) {
+        if self.status_shown {
+            self.status = None;
+        }
+    }
+
+    // Scales the windows of every tab proportionally to the new terminal size.
+    pub fn resize(&mut self, size: Size) {
+        let old = self.layout_size();
+        self.size = size;
+        self.rescale_windows(old);
+    }
+
+    // Shows or hides the status line, and gives its row to the windows or takes it from them.
+    pub fn set_laststatus(&mut self, laststatus: u8) {
+        let old = self.layout_size();
+        self.settings.laststatus = laststatus;
+        self.rescale_windows(old);
+    }
+
+    // Scales the windows of every tab from the old layout size to the current one.
+    fn rescale_windows(&mut self, old: Size) {
+        let new = self.layout_size();
+        let ids: Vec<WindowID> =
+            self.tabs.iter().flat_map(|tab| tab.open_windows.iter().copied()).collect();
+        for id in ids {
+            let window = &self.windows[id];
+            let left = util::rescale(window.position.x, old.width, new.width);
+            let right = util::rescale(window.position.x + window.size.width, old.width, new.width);
+            let top = util::rescale(window.position.y, old.height, new.height);
+            let bottom =
+                util::rescale(window.position.y + window.size.height, old.height, new.height);
+            let size = Size { width: right - left, height: bottom - top };
+            self.set_window_area(id, Position { x: left, y: top }, size);
+        }
+        self.force_redraw();
+    }
+
+    fn set_window_area(&mut self, id: WindowID, position: Position, size: Size) {
+        let window = &mut self.windows[id];
+        window.position = position;
+        window.size = size;
+        window.redraw = true;
+        self.clamp_cursor(id);
     }
 
     pub fn force_redraw(&mut self) {
-        for window in &mut self.windows.underlying {
+        for window in self.windows.iter_mut() {
             window.redraw = true;
         }
     }
@@ -155,17 +616,25 @@ impl Editor {
         self.tabs[self.current_tab].window_focus
     }
 
-    pub fn window_ids(&self) -> impl Iterator<Item = WindowID> {
-        (0..self.windows.len()).map(crate::indexvec::VecIndex::new)
+    pub fn window_ids(&self) -> impl Iterator<Item = WindowID> + '_ {
+        self.windows.indices()
     }
 
     pub fn new_window(&mut self) -> WindowID {
-        self.window_ids()
-            .find(|&id| !self.windows[id].is_open)
-            .unwrap_or_else(|| self.windows.push(Window::default()))
+        let closed = self.window_ids().find(|&id| !self.windows[id].is_open);
+        closed.unwrap_or_else(|| self.windows.push(Window::default()))
+    }
+
+    // Remembers the cursor in the window's buffer, to be restored when the buffer is shown again.
+    fn remember_cursor(&mut self, id: WindowID) {
+        let window = &self.windows[id];
+        if let Some(view) = window.view {
+            self.buffers[view.buffer].cursor = window.cursor;
+        }
     }
 
-    fn set_window_focus(&mut self, new_focus: WindowID) {
+    pub fn set_window_focus(&mut self, new_focus: WindowID) {
+        self.remember_cursor(self.window_focus());
         let tab = &mut self.tabs[self.current_tab];
         self.windows[tab.window_focus].redraw = true;
         self.windows[new_focus].redraw = true;
@@ -178,17 +647,356 @@ impl Editor {
         self.force_redraw();
     }
 
-    // TODO: check if already open
+    fn find_buffer(&self, path: &std::path::Path) -> Option<BufferID> {
+        let path = std::fs::canonicalize(path).ok()?;
+        self.buffers.indices().find(|&id| {
+            self.buffers[id].file_info.as_ref().is_some_and(|info| {
+                std::fs::canonicalize(&info.path).is_ok_and(|buffer_path| buffer_path == path)
+            })
+        })
+    }
+
     pub fn edit(&mut self, path: PathBuf) -> io::Result<()> {
-        let buffer = self.buffers.push(Buffer::read(path)?);
-        let window = &mut self.windows[self.tabs[self.current_tab].window_focus];
-        let size = Size { width: window.size.width - 2, height: window.size.height - 2 };
-        window.view = Some(View { offset: window.position.x + 1, size, buffer });
-        window.cursor = Position::default();
+        if path.is_dir() {
+            return self.explore(path);
+        }
+        let buffer = match self.find_buffer(&path) {
+            Some(buffer) => buffer,
+            None => {
+                let display = path.display().to_string();
+                let buffer = Buffer::read(path).map_err(|error| {
+                    io::Error::new(
+                        error.kind(),
+                        format!("E212: Can't open file for reading: {display}"),
+                    )
+                })?;
+                let name = buffer.file_info.as_ref().map(FileInfo::name).unwrap_or_default();
+                let swap = buffer.file_info.as_ref().is_some_and(|info| info.swap_path().exists());
+                let message = match (buffer.converted, swap) {
+                    (true, true) => Some(format!(
+                        "\"{name}\" [converted], found a swap file, :recover to restore it"
+                    )),
+                    (true, false) => Some(format!("\"{name}\" [converted]")),
+                    (false, true) => {
+                        Some(format!("Found a swap file for \"{name}\", :recover to restore it"))
+                    }
+                    (false, false) => None,
+                };
+                if let Some(message) = message {
+                    self.emit_message(message);
+                }
+                self.buffers.push(buffer)
+            }
+        };
+        self.show_buffer(self.window_focus(), buffer);
+        Ok(())
+    }
+
+    // Reloads the buffers without unsaved changes whose files were modified since they were read.
+    pub fn reload_changed_files(&mut self) {
+        let ids: Vec<BufferID> = self.buffers.indices().collect();
+        for id in ids {
+            let buffer = &self.buffers[id];
+            let Some(info) = buffer.file_info.as_ref().filter(|_| !buffer.modified)
+            else {
+                continue;
+            };
+            let time = std::fs::metadata(&info.path).and_then(|metadata| metadata.modified());
+            if !time.is_ok_and(|time| time > info.time) {
+                continue;
+            }
+            let Ok(reloaded) = Buffer::read(info.path.clone())
+            else {
+                continue;
+            };
+            let name = info.name();
+            let buffer = &mut self.buffers[id];
+            buffer.text = reloaded.text;
+            buffer.file_info = reloaded.file_info;
+            buffer.converted = reloaded.converted;
+            buffer.line_ending = reloaded.line_ending;
+            buffer.no_end_of_line = reloaded.no_end_of_line;
+            self.text_replaced(id);
+            self.emit_message(format!("\"{name}\" file changed on disk, reloaded"));
+        }
+    }
+
+    // Keeps the cursors of the windows viewing the buffer on its text after it has changed.
+    fn text_replaced(&mut self, buffer: BufferID) {
+        let windows: Vec<WindowID> = self.windows.indices().collect();
+        for window in windows {
+            if self.windows[window].view.is_some_and(|view| view.buffer == buffer) {
+                self.clamp_cursor(window);
+            }
+        }
+        self.redraw_buffer(buffer);
+    }
+
+    // Removes trailing whitespace from the focused window's buffer.
+    pub fn trim_trailing_whitespace(&mut self) {
+        let Some(view) = self.windows[self.window_focus()].view
+        else {
+            return;
+        };
+        let buffer = view.buffer;
+        if !self.buffers[buffer].settings.modifiable {
+            self.emit_message(String::from("Buffer is not modifiable"));
+            return;
+        }
+        let trimmed = self.buffers[buffer].trim_trailing_whitespace();
+        self.text_replaced(buffer);
+        let lines = if trimmed == 1 { "line" } else { "lines" };
+        self.emit_message(format!("Trimmed trailing whitespace on {trimmed} {lines}"));
+    }
+
+    // Undoes up to `count` changes in the focused window's buffer, or redoes them with `redo`.
+    pub fn undo(&mut self, count: usize, redo: bool) {
+        let Some(view) = self.windows[self.window_focus()].view
+        else {
+            return;
+        };
+        let buffer = &mut self.buffers[view.buffer];
+        let step = if redo { text::PieceTable::redo } else { text::PieceTable::undo };
+        let moved = (0..count).take_while(|_| step(&mut buffer.text)).count();
+        if moved == 0 {
+            let end = if redo { "newest" } else { "oldest" };
+            self.emit_message(format!("Already at {end} change"));
+            return;
+        }
+        (buffer.modified, buffer.swap_pending) = (true, true);
+        self.text_replaced(view.buffer);
+        let changes = if moved == 1 { "change" } else { "changes" };
+        let direction = if redo { "later" } else { "earlier" };
+        self.emit_message(format!("{moved} {changes} {direction}"));
+    }
+
+    // Reports how many changes in the focused window's buffer can be undone and redone.
+    pub fn show_undo_list(&mut self) {
+        if let Some(view) = self.windows[self.window_focus()].view {
+            let (undo, redo) = self.buffers[view.buffer].text.undo_counts();
+            self.emit_message(format!("Changes: {undo} to undo, {redo} to redo"));
+        }
+    }
+
+    // Shows the text in a scratch buffer in the focused window. A scratch buffer with the same
+    // name is reused, and its text is replaced.
+    pub fn show_scratch(&mut self, name: &'static str, text: &str) {
+        let existing = self.buffers.indices().find(|&id| self.buffers[id].scratch == Some(name));
+        let buffer = match existing {
+            Some(buffer) => {
+                self.buffers[buffer].text = text.to_owned().into();
+                self.text_replaced(buffer);
+                buffer
+            }
+            None => {
+                let settings = settings::BufferSettings { modifiable: false, ..Default::default() };
+                self.buffers.push(Buffer {
+                    text: text.to_owned().into(),
+                    settings,
+                    scratch: Some(name),
+                    ..Buffer::default()
+                })
+            }
+        };
+        self.show_buffer(self.window_focus(), buffer);
+    }
+
+    // Deletes the focused window's buffer if it is a scratch buffer.
+    pub fn close_scratch(&mut self) {
+        let view = self.windows[self.window_focus()].view;
+        if view.is_some_and(|view| self.buffers[view.buffer].scratch.is_some()) {
+            self.delete_buffer(None, true).ok();
+        }
+    }
+
+    // Lists the entries of the directory in the "Explore" scratch buffer, sorted by name, with a
+    // slash after directories and the parent directory first.
+    pub fn explore(&mut self, directory: PathBuf) -> io::Result<()> {
+        let directory = directory.canonicalize().unwrap_or(directory);
+        let error = |error: io::Error| {
+            let display = directory.display();
+            io::Error::new(error.kind(), format!("Can't read directory: {display}"))
+        };
+        let mut entries = Vec::new();
+        for entry in std::fs::read_dir(&directory).map_err(error)? {
+            let path = entry.map_err(error)?.path();
+            let mut name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+            if path.is_dir() {
+                name.push('/');
+            }
+            entries.push(name);
+        }
+        entries.sort();
+        if directory.parent().is_some() {
+            entries.insert(0, String::from("../"));
+        }
+        self.show_scratch("Explore", &entries.join("\n"));
+        let id = self.window_focus();
+        if let Some(view) = self.windows[id].view {
+            self.buffers[view.buffer].directory = Some(directory);
+        }
+        self.windows[id].cursor = Position::default();
+        Ok(())
+    }
+
+    // Edits the file or lists the directory on the cursor line of an "Explore" buffer.
+    pub fn open_directory_entry(&mut self) -> io::Result<()> {
+        let view = self.windows[self.window_focus()].view;
+        let Some(directory) = view.and_then(|view| self.buffers[view.buffer].directory.clone())
+        else {
+            return Ok(());
+        };
+        match self.cursor_line() {
+            Some((_, line)) if !line.is_empty() => {
+                self.edit(directory.join(line.iter().collect::<String>()))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // Shows a new buffer without a file in the focused window.
+    pub fn edit_new(&mut self) {
+        let buffer = self.buffers.push(Buffer::default());
+        self.show_buffer(self.window_focus(), buffer);
+    }
+
+    fn show_buffer(&mut self, id: WindowID, buffer: BufferID) {
+        self.remember_cursor(id);
+        let window = &mut self.windows[id];
+        window.view = Some(View { buffer });
+        (window.scroll_x, window.scroll_y) = (0, 0);
+        window.cursor = self.buffers[buffer].cursor;
         window.redraw = true;
+        self.clamp_cursor(id);
+    }
+
+    // Deletes the given buffer, or the focused window's buffer, showing the nearest remaining
+    // buffer in the windows that viewed it.
+    pub fn delete_buffer(&mut self, buffer: Option<BufferID>, force: bool) -> io::Result<()> {
+        let Some(buffer) =
+            buffer.or(self.windows[self.window_focus()].view.map(|view| view.buffer))
+        else {
+            return Err(io::Error::other("No buffer to delete"));
+        };
+        let Some(modified) = self.buffers.get(buffer).map(|buffer| buffer.modified)
+        else {
+            return Err(io::Error::other(format!("No buffer {}", buffer.get() + 1)));
+        };
+        if modified && !force {
+            return Err(io::Error::other(format!(
+                "No write since last change for buffer {} (add ! to override)",
+                buffer.get() + 1
+            )));
+        }
+        self.buffers[buffer].remove_swap();
+        self.buffers.remove(buffer);
+        let mut replacement = None;
+        for id in self.window_ids().collect::<Vec<_>>() {
+            if self.windows[id].view.is_some_and(|view| view.buffer == buffer) {
+                self.windows[id].view = None;
+                if self.windows[id].is_open {
+                    let replacement = *replacement.get_or_insert_with(|| {
+                        let nearest =
+                            self.buffers.indices().min_by_key(|id| id.get().abs_diff(buffer.get()));
+                        nearest.unwrap_or_else(|| self.buffers.push(Buffer::default()))
+                    });
+                    self.show_buffer(id, replacement);
+                }
+            }
+        }
         Ok(())
     }
 
+    // Writes the focused window's buffer, to `path` if given, which also becomes its file.
+    pub fn write_buffer(&mut self, path: Option<PathBuf>, force: bool) -> io::Result<()> {
+        let Some(view) = self.windows[self.window_focus()].view
+        else {
+            return Err(io::Error::other("No buffer to write"));
+        };
+        let buffer = &mut self.buffers[view.buffer];
+        let Some(path) = path.or_else(|| buffer.file_info.as_ref().map(|info| info.path.clone()))
+        else {
+            return Err(io::Error::other("No file name"));
+        };
+        let bytes = buffer.save(path, force)?;
+        let name = buffer.file_info.as_ref().map(FileInfo::name).unwrap_or_default();
+        let lines = buffer.lines();
+        self.text_replaced(view.buffer);
+        self.emit_message(format!("\"{name}\" {lines}L, {bytes}B written"));
+        Ok(())
+    }
+
+    // Writes every modified buffer, and reports how many were written.
+    pub fn write_all(&mut self, force: bool) -> io::Result<()> {
+        let mut written = 0;
+        for id in self.buffers.indices().collect::<Vec<_>>() {
+            let buffer = &mut self.buffers[id];
+            if !buffer.modified {
+                continue;
+            }
+            let Some(path) = buffer.file_info.as_ref().map(|info| info.path.clone())
+            else {
+                return Err(io::Error::other(format!("No file name for buffer {}", id.get() + 1)));
+            };
+            buffer.save(path, force)?;
+            self.text_replaced(id);
+            written += 1;
+        }
+        let buffers = if written == 1 { "buffer" } else { "buffers" };
+        self.emit_message(format!("{written} {buffers} written"));
+        Ok(())
+    }
+
+    // The file path under the cursor, and the directory of the focused window's file that it is
+    // relative to.
+    pub fn path_under_cursor(&self) -> Option<(String, Option<PathBuf>)> {
+        let (cursor, line) = self.cursor_line()?;
+        let range = textobject::path(&line, cursor.x as usize)?;
+        let view = self.windows[self.window_focus()].view?;
+        let info = self.buffers[view.buffer].file_info.as_ref();
+        let directory = info.and_then(|info| info.path.parent()).map(PathBuf::from);
+        Some((line[range].iter().collect(), directory))
+    }
+
+    // Shows the path of the focused window's buffer, its length, and where the cursor is in it.
+    pub fn show_file_info(&mut self) {
+        let window = &self.windows[self.window_focus()];
+        let Some(view) = window.view
+        else {
+            return;
+        };
+        let buffer = &self.buffers[view.buffer];
+        let name = match &buffer.file_info {
+            Some(info) => format!("\"{}\"", info.path.display()),
+            None => String::from("[No Name]"),
+        };
+        let mut modified = String::from(if buffer.modified { " [Modified]" } else { "" });
+        if buffer.no_end_of_line {
+            modified.push_str(" [noeol]");
+        }
+        let lines = buffer.lines();
+        let message = match lines {
+            0 => format!("{name}{modified} --No lines in buffer--"),
+            1 => format!("{name}{modified} 1 line --100%--"),
+            _ => {
+                let percent = ((window.cursor.y as usize + 1) * 100 / lines).min(100);
+                format!("{name}{modified} {lines} lines --{percent}%--")
+            }
+        };
+        self.emit_message(message);
+    }
+
+    // Quitting is refused while a buffer has unsaved changes.
+    pub fn check_unsaved(&self) -> io::Result<()> {
+        match self.buffers.indices().find(|&id| self.buffers[id].modified) {
+            Some(id) => Err(io::Error::other(format!(
+                "No write since last change for buffer {} (add ! to override)",
+                id.get() + 1
+            ))),
+            None => Ok(()),
+        }
+    }
+
     pub fn tab_open(&mut self) {
         let tab = self.new_tab();
         self.tabs.insert(self.current_tab + 1, tab);
@@ -209,101 +1017,3257 @@ impl Editor {
         }
     }
 
-    pub fn tab_next(&mut self) {
-        self.set_current_tab(util::rotate_forward(0, self.tabs.len(), self.current_tab));
-    }
-
-    pub fn tab_previous(&mut self) {
-        self.set_current_tab(util::rotate_backward(0, self.tabs.len(), self.current_tab));
-    }
-
-    pub fn move_cursor(&mut self, direction: util::Direction) {
-        let window = &mut self.windows[self.tabs[self.current_tab].window_focus];
-        window.cursor = window.cursor.move_toward(direction);
-        window.keep_cursor_within_bounds();
+    pub fn tab_move(&mut self, index: usize) {
+        let index = index.min(self.tabs.len() - 1);
+        let tab = self.tabs.remove(self.current_tab);
+        self.tabs.insert(index, tab);
+        self.set_current_tab(index);
     }
 
-    pub fn rotate_focus_forward(&mut self) {
-        let index = crate::indexvec::VecIndex::get(self.window_focus());
-        let index = util::rotate_forward(0, self.windows.len(), index);
-        self.set_window_focus(crate::indexvec::VecIndex::new(index));
+    pub fn goto_tab(&mut self, index: usize) {
+        if index < self.tabs.len() {
+            self.set_current_tab(index);
+        }
+        else {
+            self.emit_message(format!("Tab {} does not exist", index + 1));
+        }
     }
 
-    pub fn rotate_focus_backward(&mut self) {
-        let index = crate::indexvec::VecIndex::get(self.window_focus());
-        let index = util::rotate_backward(0, self.windows.len(), index);
-        self.set_window_focus(crate::indexvec::VecIndex::new(index));
+    pub fn tab_next(&mut self) {
+        self.set_current_tab(util::rotate_forward(0, self.tabs.len(), self.current_tab));
     }
 
-    fn run_cursor_focus_beam(&self, beam: impl Iterator<Item = Position>) -> Option<WindowID> {
-        let window_focus = self.window_focus();
-        beam.flat_map(|position| {
-            self.tabs[self.current_tab]
-                .open_windows
-                .iter()
-                .copied()
-                .filter(move |&id| id != window_focus && self.windows[id].contains(position))
-        })
-        .next()
+    pub fn tab_previous(&mut self) {
+        self.set_current_tab(util::rotate_backward(0, self.tabs.len(), self.current_tab));
     }
 
-    fn send_cursor_focus_beam(&self, direction: util::Direction) -> Option<WindowID> {
-        let window = &self.windows[self.tabs[self.current_tab].window_focus];
-        let cursor = window.position.offset(window.cursor);
-
-        match direction {
-            util::Direction::Up => {
-                let range = 0..window.position.y;
-                self.run_cursor_focus_beam(range.rev().map(|y| Position { x: cursor.x, y }))
-            }
-            util::Direction::Down => {
-                let range = window.position.y..self.size.height;
-                self.run_cursor_focus_beam(range.map(|y| Position { x: cursor.x, y }))
+    pub fn set_mode(&mut self, mode: Mode) {
+        if self.mode == Mode::Insert && mode != Mode::Insert {
+            if let Some(block) = self.block_insert.take() {
+                self.repeat_block_insert(block);
             }
-            util::Direction::Left => {
-                let range = 0..window.position.x;
-                self.run_cursor_focus_beam(range.rev().map(|x| Position { x, y: cursor.y }))
+            let text = std::mem::take(&mut self.insertion);
+            if let Some(change) = self.insert_change.take() {
+                self.last_change = Some(Change::InsertAfter(Box::new(change), text));
             }
-            util::Direction::Right => {
-                let range = window.position.x + window.size.width..self.size.width;
-                self.run_cursor_focus_beam(range.map(|x| Position { x, y: cursor.y }))
+            else if !text.is_empty() {
+                self.last_change = Some(Change::Insert(text));
             }
         }
+        self.undo_group = false;
+        self.mode = mode;
+        self.clamp_cursor(self.window_focus());
     }
 
-    pub fn move_focus(&mut self, direction: util::Direction) {
-        self.send_cursor_focus_beam(direction).inspect(|&id| self.set_window_focus(id));
+    // The last line the cursor can be on. The empty line after a final newline is not a line of
+    // the buffer, but typing a newline at the end in insert mode leads there.
+    fn last_cursor_line(&self, buffer: BufferID) -> usize {
+        let buffer = &self.buffers[buffer];
+        match self.mode {
+            Mode::Insert | Mode::Replace => buffer.text.line_count() - 1,
+            _ => buffer.lines().max(1) - 1,
+        }
     }
 
-    pub fn vertical_split_window(&mut self) {
-        let left = &mut self.windows[self.tabs[self.current_tab].window_focus];
-        if left.size.width < 6 {
-            self.emit_message(String::from("The window is too small for a vertical split"));
+    // Keeps the cursor on the text of the window's buffer, and scrolls the cursor into view.
+    pub fn clamp_cursor(&mut self, id: WindowID) {
+        let Some(view) = self.windows[id].view
+        else {
+            self.windows[id].cursor = Position::default();
             return;
+        };
+        let last_line = self.last_cursor_line(view.buffer).min(u16::MAX as usize) as u16;
+        let window = &mut self.windows[id];
+        let text = &self.buffers[view.buffer].text;
+        window.cursor.y = window.cursor.y.min(last_line);
+        let length = text.line_length(window.cursor.y as usize).min(u16::MAX as usize) as u16;
+        let max_x = match self.mode {
+            Mode::Insert | Mode::Replace => length,
+            _ => length.saturating_sub(1),
+        };
+        window.cursor.x = window.cursor.x.min(max_x);
+        let height = window.size.height.saturating_sub(2);
+        let min_scroll = window.cursor.y.saturating_sub(height.saturating_sub(1));
+        let scroll = window.scroll_y.clamp(min_scroll, window.cursor.y);
+        if scroll != window.scroll_y {
+            window.scroll_y = scroll;
+            window.redraw = true;
         }
-        let remainder = left.size.width % 2;
-        left.size.width /= 2;
-        left.keep_cursor_within_bounds();
-        let mut right: Window = *left;
-        right.size.width += remainder;
-        right.position.x += left.size.width;
-        (left.redraw, right.redraw) = (true, true);
-        self.tabs[self.current_tab].open_windows.push(self.windows.push(right));
+        self.scroll_horizontally(id);
     }
 
-    pub fn horizontal_split_window(&mut self) {
-        let above = &mut self.windows[self.tabs[self.current_tab].window_focus];
-        if above.size.height < 6 {
-            self.emit_message(String::from("The window is too small for a horizontal split"));
+    // Scrolls the view sideways to keep the cursor `sidescrolloff` columns away from its edges,
+    // or as far as the width of the view allows.
+    fn scroll_horizontally(&mut self, id: WindowID) {
+        let Window { cursor, size, view: Some(view), scroll_x, .. } = self.windows[id]
+        else {
             return;
+        };
+        let gutter = self.windows[id].gutter_width(&self.buffers[view.buffer]);
+        let width = size.width.saturating_sub(2).saturating_sub(gutter) as usize;
+        let column = self.display_column(view.buffer, cursor.y as usize, cursor.x as usize);
+        let margin = (self.settings.sidescrolloff as usize).min(width.saturating_sub(1) / 2);
+        let min_offset = (column + margin + 1).saturating_sub(width);
+        let max_offset = column.saturating_sub(margin).max(min_offset);
+        let offset = (scroll_x as usize).clamp(min_offset, max_offset) as u16;
+        if offset != scroll_x {
+            let window = &mut self.windows[id];
+            window.scroll_x = offset;
+            window.redraw = true;
         }
-        let remainder = above.size.height % 2;
-        above.size.height /= 2;
-        above.keep_cursor_within_bounds();
-        let mut below: Window = *above;
-        below.size.height += remainder;
-        below.position.y += above.size.height;
-        (above.redraw, below.redraw) = (true, true);
-        self.tabs[self.current_tab].open_windows.push(self.windows.push(below));
+    }
+
+    pub fn screen_cursor(&self, id: WindowID) -> Position {
+        let window = &self.windows[id];
+        let origin = window.position.offset(Position { x: 1, y: 1 });
+        match window.view {
+            Some(view) => {
+                let (line, column) = (window.cursor.y as usize, window.cursor.x as usize);
+                let x = self.display_column(view.buffer, line, column) as u16;
+                origin.offset_x(window.gutter_width(&self.buffers[view.buffer])).offset(Position {
+                    x: x.saturating_sub(window.scroll_x),
+                    y: window.cursor.y.saturating_sub(window.scroll_y),
+                })
+            }
+            None => origin,
+        }
+    }
+
+    // Redraws the windows viewing the buffer, which is marked modified if its text changed.
+    fn redraw_buffer(&mut self, buffer: BufferID) {
+        let text_buffer = &mut self.buffers[buffer];
+        if text_buffer.text.take_changed() {
+            (text_buffer.modified, text_buffer.swap_pending) = (true, true);
+        }
+        for &id in &self.tabs[self.current_tab].open_windows {
+            let window = &mut self.windows[id];
+            if window.view.is_some_and(|view| view.buffer == buffer) {
+                window.redraw = true;
+            }
+        }
+    }
+
+    // The buffer viewed by the focused window and the byte offset of its cursor.
+    fn cursor_edit_target(&mut self) -> Option<(BufferID, usize)> {
+        let window = &self.windows[self.window_focus()];
+        let view = window.view?;
+        let buffer = &mut self.buffers[view.buffer];
+        if !buffer.settings.modifiable {
+            self.emit_message(String::from("Buffer is not modifiable"));
+            return None;
+        }
+        if !self.undo_group {
+            buffer.text.checkpoint();
+        }
+        self.undo_group = matches!(self.mode, Mode::Insert | Mode::Replace);
+        let offset = buffer.text.byte_offset_of(window.cursor.y as usize, window.cursor.x as usize);
+        let (_, column) = buffer.text.position_of(offset);
+        self.windows[self.tabs[self.current_tab].window_focus].cursor.x = column as u16;
+        Some((view.buffer, offset))
+    }
+
+    fn insert_at_cursor(&mut self, buffer: BufferID, offset: usize, character: char) {
+        self.buffers[buffer].text.insert(offset, character.encode_utf8(&mut [0; 4]));
+        let window = &mut self.windows[self.tabs[self.current_tab].window_focus];
+        window.cursor = match character {
+            '\n' => Position { x: 0, y: window.cursor.y + 1 },
+            _ => window.cursor.offset_x(1),
+        };
+        self.clamp_cursor(self.window_focus());
+        self.redraw_buffer(buffer);
+    }
+
+    pub fn insert_char(&mut self, character: char) {
+        if let Some((buffer, offset)) = self.cursor_edit_target() {
+            self.insert_at_cursor(buffer, offset, character);
+            if self.mode == Mode::Insert {
+                self.insertion.push(character);
+            }
+        }
+    }
+
+    // Breaks the line at the cursor. With `autoindent`, the new line gets the indentation of
+    // the old one, except for any indentation that is already after the cursor.
+    pub fn insert_newline(&mut self) {
+        let window = &self.windows[self.window_focus()];
+        let mut indentation = String::new();
+        if let Some(view) = window.view.filter(|_| window.settings.autoindent) {
+            let text = &self.buffers[view.buffer].text;
+            let mut offset = text.byte_offset_of(window.cursor.y as usize, 0);
+            while let Some(character @ (' ' | '\t')) = text.char_at(offset) {
+                if indentation.len() == window.cursor.x as usize {
+                    break;
+                }
+                indentation.push(character);
+                offset += 1;
+            }
+        }
+        for character in std::iter::once('\n').chain(indentation.chars()) {
+            self.insert_char(character);
+        }
+    }
+
+    // The screen column of the character at `column` on the line, with tabs expanded.
+    pub fn display_column(&self, buffer: BufferID, line: usize, column: usize) -> usize {
+        let buffer = &self.buffers[buffer];
+        let mut offset = buffer.text.byte_offset_of(line, 0);
+        let mut display_column = 0;
+        for _ in 0..column {
+            let Some(character) =
+                buffer.text.char_at(offset).filter(|&character| character != '\n')
+            else {
+                break;
+            };
+            offset += character.len_utf8();
+            display_column = match character {
+                '\t' => util::next_tab_stop(display_column, buffer.settings.tabstop),
+                _ => display_column + 1,
+            };
+        }
+        display_column
+    }
+
+    // The column of the character that covers the screen column on the line, with tabs expanded.
+    fn column_at_display(&self, buffer: BufferID, line: usize, display_column: usize) -> usize {
+        let buffer = &self.buffers[buffer];
+        let mut offset = buffer.text.byte_offset_of(line, 0);
+        let (mut column, mut start) = (0, 0);
+        while let Some(character) =
+            buffer.text.char_at(offset).filter(|&character| character != '\n')
+        {
+            let end = match character {
+                '\t' => util::next_tab_stop(start, buffer.settings.tabstop),
+                _ => start + 1,
+            };
+            if display_column < end {
+                break;
+            }
+            (offset, column, start) = (offset + character.len_utf8(), column + 1, end);
+        }
+        column
+    }
+
+    fn window_at(&self, position: Position) -> Option<WindowID> {
+        let tab = &self.tabs[self.current_tab];
+        tab.open_windows.iter().copied().find(|&id| self.windows[id].contains(position))
+    }
+
+    // Focuses the window at the screen position, and moves its cursor to the clicked cell.
+    pub fn click(&mut self, position: Position) {
+        let Some(id) = self.window_at(position)
+        else {
+            return;
+        };
+        self.set_window_focus(id);
+        let window = &self.windows[id];
+        let Some(view) = window.view
+        else {
+            return;
+        };
+        let origin = self.windows[id]
+            .position
+            .offset(Position { x: 1 + window.gutter_width(&self.buffers[view.buffer]), y: 1 });
+        let line = position.y.saturating_sub(origin.y).saturating_add(window.scroll_y);
+        let display_column = position.x.saturating_sub(origin.x).saturating_add(window.scroll_x);
+        let column = self.column_at_display(view.buffer, line as usize, display_column as usize);
+        self.windows[id].cursor = Position { x: column as u16, y: line };
+        self.clamp_cursor(id);
+        self.remember_cursor(id);
+        self.undo_group = false;
+    }
+
+    // Scrolls the window at the screen position without moving its cursor.
+    pub fn scroll_window_at(&mut self, position: Position, direction: util::Direction) {
+        let Some(id) = self.window_at(position)
+        else {
+            return;
+        };
+        let window = &mut self.windows[id];
+        let Some(view) = window.view
+        else {
+            return;
+        };
+        let line_count = self.buffers[view.buffer].text.line_count();
+        let last_line = line_count.saturating_sub(1).min(u16::MAX as usize) as u16;
+        let scroll = match direction {
+            util::Direction::Up => window.scroll_y.saturating_sub(SCROLL_LINES),
+            util::Direction::Down => window.scroll_y.saturating_add(SCROLL_LINES).min(last_line),
+            util::Direction::Left | util::Direction::Right => window.scroll_y,
+        };
+        if scroll != window.scroll_y {
+            window.scroll_y = scroll;
+            window.redraw = true;
+        }
+    }
+
+    // Whether the window shows the line that its cursor is on.
+    pub fn cursor_visible(&self, id: WindowID) -> bool {
+        let window = &self.windows[id];
+        window.view.is_none_or(|_| {
+            let height = window.size.height.saturating_sub(2);
+            (window.scroll_y..window.scroll_y.saturating_add(height)).contains(&window.cursor.y)
+        })
+    }
+
+    // Inserts a tab, or spaces up to the next tab stop when `expandtab` is set.
+    pub fn insert_tab(&mut self) {
+        let window = &self.windows[self.window_focus()];
+        let Some(view) = window.view
+        else {
+            return;
+        };
+        let settings = self.buffers[view.buffer].settings;
+        if settings.expandtab {
+            let (line, column) = (window.cursor.y as usize, window.cursor.x as usize);
+            let display_column = self.display_column(view.buffer, line, column);
+            for _ in display_column..util::next_tab_stop(display_column, settings.tabstop) {
+                self.insert_char(' ');
+            }
+        }
+        else {
+            self.insert_char('\t');
+        }
+    }
+
+    // Starts selecting a block of text from the cursor.
+    pub fn begin_visual_block(&mut self) {
+        self.visual_anchor = self.windows[self.window_focus()].cursor;
+        self.mode = Mode::VisualBlock;
+    }
+
+    // The buffer, the lines, and the screen columns of the block selected in visual block mode.
+    pub fn visual_block(&self) -> Option<(BufferID, Range<usize>, Range<usize>)> {
+        let window = &self.windows[self.window_focus()];
+        let view = window.view.filter(|_| self.mode == Mode::VisualBlock)?;
+        let (anchor, cursor) = (self.visual_anchor, window.cursor);
+        let display = |position: Position| {
+            self.display_column(view.buffer, position.y as usize, position.x as usize)
+        };
+        let (first, second) = (display(anchor), display(cursor));
+        let lines = anchor.y.min(cursor.y) as usize..anchor.y.max(cursor.y) as usize + 1;
+        Some((view.buffer, lines, first.min(second)..first.max(second) + 1))
+    }
+
+    // The columns of the characters on the line that start within the screen columns.
+    pub fn block_columns(
+        &self,
+        buffer: BufferID,
+        line: usize,
+        columns: &Range<usize>,
+    ) -> Range<usize> {
+        let length = self.buffers[buffer].text.line_length(line);
+        let mut start = self.column_at_display(buffer, line, columns.start);
+        if self.display_column(buffer, line, start) < columns.start {
+            start += 1;
+        }
+        let end = (self.column_at_display(buffer, line, columns.end - 1) + 1).min(length);
+        start..end.max(start)
+    }
+
+    // Deletes the selected block, yanking its lines into the register.
+    pub fn delete_block(&mut self) {
+        let Some((buffer, lines, columns)) = self.visual_block()
+        else {
+            return;
+        };
+        self.set_mode(Mode::Normal);
+        if self.cursor_edit_target().is_none() {
+            return;
+        }
+        let mut yanked = Vec::new();
+        for line in lines.clone() {
+            let range = self.block_columns(buffer, line, &columns);
+            let text = &mut self.buffers[buffer].text;
+            let (start, end) =
+                (text.byte_offset_of(line, range.start), text.byte_offset_of(line, range.end));
+            yanked.push(text.slice(start..end));
+            text.remove(start, end - start);
+        }
+        self.yank(yanked.join("\n"), false);
+        let id = self.window_focus();
+        let column = self.column_at_display(buffer, lines.start, columns.start);
+        self.windows[id].cursor = Position { x: column as u16, y: lines.start as u16 };
+        self.clamp_cursor(id);
+        self.redraw_buffer(buffer);
+    }
+
+    // Inserts before the selected block with `I`, or after it with `A`. The text is typed on the
+    // first line of the block, and repeated on the other lines when insert mode ends.
+    pub fn begin_block_insert(&mut self, append: bool) {
+        let Some((buffer, lines, columns)) = self.visual_block()
+        else {
+            return;
+        };
+        self.set_mode(Mode::Normal);
+        if self.cursor_edit_target().is_none() {
+            return;
+        }
+        let display_column = if append { columns.end } else { columns.start };
+        let Some(column) = self.block_insert_column(buffer, lines.start, display_column, append)
+        else {
+            return;
+        };
+        let id = self.window_focus();
+        self.continue_in_insert();
+        self.windows[id].cursor = Position { x: column as u16, y: lines.start as u16 };
+        self.clamp_cursor(id);
+        self.redraw_buffer(buffer);
+        self.block_insert = Some((lines.start + 1..lines.end, display_column, append));
+    }
+
+    // The column on the line at the screen column. Lines that end before it are padded with
+    // spaces when appending, and skipped otherwise.
+    fn block_insert_column(
+        &mut self,
+        buffer: BufferID,
+        line: usize,
+        display_column: usize,
+        append: bool,
+    ) -> Option<usize> {
+        let length = self.buffers[buffer].text.line_length(line);
+        let width = self.display_column(buffer, line, length);
+        if width >= display_column {
+            return Some(self.column_at_display(buffer, line, display_column));
+        }
+        if !append {
+            return None;
+        }
+        let text = &mut self.buffers[buffer].text;
+        text.insert(text.byte_offset_of(line, length), &" ".repeat(display_column - width));
+        Some(length + display_column - width)
+    }
+
+    fn repeat_block_insert(
+        &mut self,
+        (lines, display_column, append): (Range<usize>, usize, bool),
+    ) {
+        let Some(view) = self.windows[self.window_focus()].view
+        else {
+            return;
+        };
+        if self.insertion.is_empty() || self.insertion.contains('\n') {
+            return;
+        }
+        for line in lines {
+            if let Some(column) =
+                self.block_insert_column(view.buffer, line, display_column, append)
+            {
+                let text = &mut self.buffers[view.buffer].text;
+                text.insert(text.byte_offset_of(line, column), &self.insertion);
+            }
+        }
+        self.redraw_buffer(view.buffer);
+    }
+
+    pub fn begin_insert(&mut self) {
+        self.insertion.clear();
+        self.insert_change = None;
+        self.mode = Mode::Insert;
+    }
+
+    // Enters insert mode after a change, which is undone together with the inserted text.
+    fn continue_in_insert(&mut self) {
+        self.begin_insert();
+        self.undo_group = true;
+    }
+
+    // Enters insert mode after the cursor with `a`, at the end of the line with `A`, or at the
+    // first non-blank character of the line with `I`.
+    pub fn begin_insert_at(&mut self, key: char) {
+        self.begin_insert();
+        let Some((cursor, line)) = self.cursor_line()
+        else {
+            return;
+        };
+        let column = match key {
+            'a' => (cursor.x as usize + 1).min(line.len()),
+            'A' => line.len(),
+            'I' => {
+                line.iter().position(|character| !character.is_whitespace()).unwrap_or(line.len())
+            }
+            _ => cursor.x as usize,
+        };
+        let id = self.window_focus();
+        self.windows[id].cursor.x = column as u16;
+        self.clamp_cursor(id);
+    }
+
+    // Removes up to `count` characters from the cursor to the end of the line.
+    pub fn delete_chars(&mut self, count: usize) {
+        let Some((buffer, offset)) = self.cursor_edit_target()
+        else {
+            return;
+        };
+        let cursor = self.windows[self.window_focus()].cursor;
+        let text = &mut self.buffers[buffer].text;
+        let end = text.byte_offset_of(cursor.y as usize, cursor.x as usize + count);
+        let deleted = text.slice(offset..end);
+        text.remove(offset, end - offset);
+        if !deleted.is_empty() {
+            self.yank(deleted, false);
+        }
+        self.clamp_cursor(self.window_focus());
+        self.redraw_buffer(buffer);
+        self.last_change = Some(Change::DeleteChars(count));
+    }
+
+    // Swaps the case of `count` characters from the cursor, within the cursor line, and moves the
+    // cursor past them. Some characters change length, like `ß`, which becomes `SS`.
+    pub fn toggle_case(&mut self, count: usize) {
+        let Some((buffer, offset)) = self.cursor_edit_target()
+        else {
+            return;
+        };
+        let id = self.window_focus();
+        let cursor = self.windows[id].cursor;
+        let text = &mut self.buffers[buffer].text;
+        let end = text.byte_offset_of(cursor.y as usize, (cursor.x as usize).saturating_add(count));
+        let mut toggled = String::new();
+        for character in text.slice(offset..end).chars() {
+            if character.is_uppercase() {
+                toggled.extend(character.to_lowercase());
+            }
+            else {
+                toggled.extend(character.to_uppercase());
+            }
+        }
+        text.replace(offset, end - offset, &toggled);
+        self.windows[id].cursor.x = cursor.x.saturating_add(toggled.chars().count() as u16);
+        self.clamp_cursor(id);
+        self.redraw_buffer(buffer);
+        self.last_change = Some(Change::ToggleCase(count));
+    }
+
+    // Replaces `count` characters from the cursor with the character, like `r`, and leaves the
+    // cursor on the last one. Nothing is replaced if the line has fewer characters left. A line
+    // break replaces them all with one line break, which splits the line.
+    pub fn replace_chars(&mut self, character: char, count: usize) {
+        let Some((cursor, line)) = self.cursor_line()
+        else {
+            return;
+        };
+        let end_column = (cursor.x as usize).saturating_add(count);
+        if end_column > line.len() {
+            return;
+        }
+        let Some((buffer, offset)) = self.cursor_edit_target()
+        else {
+            return;
+        };
+        let text = &mut self.buffers[buffer].text;
+        let end = text.byte_offset_of(cursor.y as usize, end_column);
+        let replacement = match character {
+            '\n' => String::from('\n'),
+            _ => character.to_string().repeat(count),
+        };
+        text.replace(offset, end - offset, &replacement);
+        let id = self.window_focus();
+        self.windows[id].cursor = match character {
+            '\n' => Position { x: 0, y: cursor.y + 1 },
+            _ => Position { x: end_column as u16 - 1, ..cursor },
+        };
+        self.redraw_buffer(buffer);
+        self.last_change = Some(Change::ReplaceChars(character, count));
+    }
+
+    // Removes `count` lines starting from the cursor line.
+    pub fn delete_lines(&mut self, count: usize) {
+        let Some((buffer, _)) = self.cursor_edit_target()
+        else {
+            return;
+        };
+        let line = self.windows[self.window_focus()].cursor.y as usize;
+        self.yank_lines(count);
+        let text = &mut self.buffers[buffer].text;
+        let mut start = text.byte_offset_of(line, 0);
+        let end = text.byte_offset_of(line.saturating_add(count), 0);
+        // Removing the last line also removes the newline that precedes it.
+        if line.saturating_add(count) >= text.line_count() {
+            start = start.saturating_sub(1);
+        }
+        text.remove(start, end - start);
+        self.windows[self.tabs[self.current_tab].window_focus].cursor.x = 0;
+        self.clamp_cursor(self.window_focus());
+        self.redraw_buffer(buffer);
+        self.last_change = Some(Change::DeleteLines(count));
+    }
+
+    // Indents `count` lines from the cursor line by one level with `>>`, or removes up to one
+    // level of indentation with `<<`.
+    pub fn shift_lines(&mut self, count: usize, indent: bool) {
+        let Some((buffer, _)) = self.cursor_edit_target()
+        else {
+            return;
+        };
+        let line = self.windows[self.window_focus()].cursor.y as usize;
+        self.shift_line_range(buffer, line..line.saturating_add(count), indent);
+        self.last_change = Some(Change::ShiftLines(count, indent));
+    }
+
+    // Shifts the lines of the block selected in visual block mode.
+    pub fn shift_block(&mut self, indent: bool) {
+        let Some((buffer, lines, _)) = self.visual_block()
+        else {
+            return;
+        };
+        self.set_mode(Mode::Normal);
+        if self.cursor_edit_target().is_none() {
+            return;
+        }
+        let id = self.window_focus();
+        self.windows[id].cursor.y = lines.start as u16;
+        self.shift_line_range(buffer, lines, indent);
+    }
+
+    // A level of indentation is a tab, or `tabstop` spaces with `expandtab`. Empty lines are not
+    // indented. The cursor stays on the same character of its line.
+    fn shift_line_range(&mut self, buffer: BufferID, lines: Range<usize>, indent: bool) {
+        let settings = self.buffers[buffer].settings;
+        let unit = if settings.expandtab {
+            " ".repeat(settings.tabstop as usize)
+        }
+        else {
+            String::from("\t")
+        };
+        let id = self.window_focus();
+        let text = &mut self.buffers[buffer].text;
+        for line in lines.start..lines.end.min(text.line_count()) {
+            let start = text.byte_offset_of(line, 0);
+            let shift = if indent {
+                if text.line_length(line) == 0 {
+                    continue;
+                }
+                text.insert(start, &unit);
+                unit.len() as isize
+            }
+            else {
+                let mut width = 0;
+                while width < settings.tabstop as usize && text.char_at(start + width) == Some(' ')
+                {
+                    width += 1;
+                }
+                if width == 0 && text.char_at(start) == Some('\t') {
+                    width = 1;
+                }
+                text.remove(start, width);
+                -(width as isize)
+            };
+            let cursor = &mut self.windows[id].cursor;
+            if cursor.y as usize == line {
+                cursor.x = (cursor.x as isize + shift).max(0) as u16;
+            }
+        }
+        self.clamp_cursor(id);
+        self.redraw_buffer(buffer);
+    }
+
+    // Sorts the lines of the focused window's buffer, or the given lines, as one change.
+    pub fn sort_lines(&mut self, lines: Option<Range<usize>>, sort: Sort) {
+        let Some((buffer, _)) = self.cursor_edit_target()
+        else {
+            return;
+        };
+        let last = self.buffers[buffer].lines().max(1);
+        let lines = lines.map_or(0..last, |lines| lines.start.min(last - 1)..lines.end.min(last));
+        let text = &mut self.buffers[buffer].text;
+        let (start, end) =
+            (text.byte_offset_of(lines.start, 0), text.byte_offset_of(lines.end - 1, usize::MAX));
+        let old = text.slice(start..end);
+        let mut sorted: Vec<&str> = old.split('\n').collect();
+        if sort.numeric {
+            sorted.sort_by_key(|line| first_number(line));
+        }
+        else {
+            sorted.sort_unstable();
+        }
+        if sort.unique {
+            sorted.dedup();
+        }
+        if sort.reverse {
+            sorted.reverse();
+        }
+        text.replace(start, end - start, &sorted.join("\n"));
+        self.text_replaced(buffer);
+    }
+
+    // Deletes the lines that match the pattern, or those that do not with `invert`, like
+    // `:g/pattern/d` and `:v/pattern/d`. Only the given lines are searched, if any.
+    pub fn delete_matching_lines(
+        &mut self,
+        lines: Option<Range<usize>>,
+        pattern: &str,
+        invert: bool,
+    ) {
+        let Some(view) = self.windows[self.window_focus()].view
+        else {
+            return;
+        };
+        let matcher = match self.compile_pattern(pattern) {
+            Ok(matcher) => matcher,
+            Err(error) => {
+                self.emit_message(error);
+                return;
+            }
+        };
+        let buffer = &self.buffers[view.buffer];
+        let lines = lines.unwrap_or(0..buffer.lines());
+        let matching: Vec<usize> = (lines.start..lines.end.min(buffer.lines()))
+            .filter(|&line| matcher.find_all(&buffer.text.line(line)).is_empty() == invert)
+            .collect();
+        if matching.is_empty() {
+            self.emit_message(format!("Pattern not found: {pattern}"));
+            return;
+        }
+        if self.cursor_edit_target().is_none() {
+            return;
+        }
+        let text = &mut self.buffers[view.buffer].text;
+        for &line in matching.iter().rev() {
+            let mut start = text.byte_offset_of(line, 0);
+            let end = text.byte_offset_of(line + 1, 0);
+            // Removing the last line also removes the newline that precedes it.
+            if line + 1 >= text.line_count() {
+                start = start.saturating_sub(1);
+            }
+            text.remove(start, end - start);
+        }
+        self.text_replaced(view.buffer);
+        let count = matching.len();
+        self.emit_message(format!("Deleted {count} {}", if count == 1 { "line" } else { "lines" }));
+    }
+
+    // Replaces the first match of the pattern on the cursor line, or on the given lines, or every
+    // match with `global`, as one change. An empty pattern is the last search pattern.
+    pub fn substitute(
+        &mut self,
+        lines: Option<Range<usize>>,
+        pattern: &str,
+        replacement: &str,
+        global: bool,
+    ) {
+        let id = self.window_focus();
+        let (Some(view), Some(pattern)) = (
+            self.windows[id].view,
+            Some(pattern).filter(|pattern| !pattern.is_empty()).or(self.search.as_deref()),
+        )
+        else {
+            self.emit_message(String::from("No previous search pattern"));
+            return;
+        };
+        let matcher = match self.compile_pattern(pattern) {
+            Ok(matcher) => matcher,
+            Err(error) => {
+                self.emit_message(error);
+                return;
+            }
+        };
+        let buffer = &self.buffers[view.buffer];
+        let cursor_line = self.windows[id].cursor.y as usize;
+        let lines = lines.map_or(cursor_line..cursor_line + 1, |lines| {
+            lines.start..lines.end.min(buffer.lines().max(1))
+        });
+        let substituted: Vec<(usize, String, usize)> = lines
+            .map(|line| {
+                let (replaced, count) =
+                    matcher.substitute(&buffer.text.line(line), replacement, global);
+                (line, replaced, count)
+            })
+            .filter(|&(_, _, count)| count != 0)
+            .collect();
+        if substituted.is_empty() {
+            self.emit_message(format!("Pattern not found: {pattern}"));
+            return;
+        }
+        if self.cursor_edit_target().is_none() {
+            return;
+        }
+        let text = &mut self.buffers[view.buffer].text;
+        for (line, replaced, _) in substituted.iter().rev() {
+            let start = text.byte_offset_of(*line, 0);
+            let end = text.byte_offset_of(*line, usize::MAX);
+            text.replace(start, end - start, replaced);
+        }
+        self.text_replaced(view.buffer);
+        let count: usize = substituted.iter().map(|&(_, _, count)| count).sum();
+        let substitutions = if count == 1 { "substitution" } else { "substitutions" };
+        let lines = if substituted.len() == 1 { "line" } else { "lines" };
+        self.emit_message(format!("{count} {substitutions} on {} {lines}", substituted.len()));
+    }
+
+    // Joins `count` lines starting from the cursor line, replacing each line break and the
+    // indentation after it with a single space.
+    pub fn join_lines(&mut self, count: usize) {
+        let window = &self.windows[self.window_focus()];
+        let Some(view) = window.view
+        else {
+            return;
+        };
+        let line = window.cursor.y as usize;
+        // The empty line after a final newline is not joined to the last line.
+        let mut lines = self.buffers[view.buffer].lines();
+        if line + 1 >= lines {
+            return;
+        }
+        let Some((buffer, _)) = self.cursor_edit_target()
+        else {
+            return;
+        };
+        let text = &mut self.buffers[buffer].text;
+        let mut column = None;
+        for _ in 0..count.saturating_sub(1).max(1) {
+            if line + 1 >= lines {
+                break;
+            }
+            lines -= 1;
+            let end = text.byte_offset_of(line, usize::MAX);
+            let mut next = end + 1;
+            while text.char_at(next).is_some_and(|character| character == ' ' || character == '\t')
+            {
+                next += 1;
+            }
+            let separator = if matches!(text.char_at(next), None | Some('\n')) { "" } else { " " };
+            text.replace(end, next - end, separator);
+            column = Some(text.position_of(end).1);
+        }
+        if let Some(column) = column {
+            self.windows[self.tabs[self.current_tab].window_focus].cursor.x = column as u16;
+            self.clamp_cursor(self.window_focus());
+            self.redraw_buffer(buffer);
+            self.last_change = Some(Change::JoinLines(count));
+        }
+    }
+
+    // The cursor of the focused window and the characters of its line.
+    fn cursor_line(&self) -> Option<(Position, Vec<char>)> {
+        let window = &self.windows[self.window_focus()];
+        let text = &self.buffers[window.view?.buffer].text;
+        Some((window.cursor, text.line(window.cursor.y as usize).chars().collect()))
+    }
+
+    // Applies the operator to the text object around the cursor on the cursor line, like `diw`.
+    pub fn apply_to_text_object(&mut self, operator: Operator, object: char, around: bool) {
+        let Some((cursor, line)) = self.cursor_line()
+        else {
+            return;
+        };
+        if let Some(columns) = textobject::find(&line, cursor.x as usize, object, around) {
+            if self.apply_to_columns(operator, &line, columns) {
+                self.record_change(operator, Change::Object(operator, object, around));
+            }
+        }
+    }
+
+    // Applies the operator from the cursor to the end of `count` words with `w`, or to the end of
+    // the line with `$`. Like in vim, `cw` does not change the whitespace after the words.
+    pub fn apply_to_motion(&mut self, operator: Operator, motion: char, count: usize) {
+        let Some((cursor, line)) = self.cursor_line()
+        else {
+            return;
+        };
+        let column = (cursor.x as usize).min(line.len());
+        let columns = match motion {
+            'w' => {
+                column..textobject::words_end(&line, column, count, operator == Operator::Change)
+            }
+            '$' => column..line.len(),
+            _ => return,
+        };
+        if self.apply_to_columns(operator, &line, columns) {
+            self.record_change(operator, Change::Motion(operator, motion, count));
+        }
+    }
+
+    // Remembers the change for `.`. A change that entered insert mode is remembered together with
+    // the text inserted after it, once insert mode is left.
+    fn record_change(&mut self, operator: Operator, change: Change) {
+        match operator {
+            Operator::Delete => self.last_change = Some(change),
+            Operator::Change => self.insert_change = Some(change),
+            Operator::Yank => {}
+        }
+    }
+
+    // Stores the text in the selected register, and in the unnamed register.
+    fn yank(&mut self, text: String, linewise: bool) {
+        let mut register = Register { text, linewise };
+        if self.register.is_ascii_uppercase() {
+            let name = self.register.to_ascii_lowercase();
+            if let Some(old) = self.registers.get(&name).filter(|old| !old.text.is_empty()) {
+                let separator = if old.linewise || linewise { "\n" } else { "" };
+                register.text = format!("{}{separator}{}", old.text, register.text);
+                register.linewise |= old.linewise;
+            }
+            self.registers.insert(name, register.clone());
+        }
+        else if self.register.is_ascii_lowercase() {
+            self.registers.insert(self.register, register.clone());
+        }
+        self.registers.insert('"', register);
+    }
+
+    // Yanks `count` lines starting from the cursor line.
+    pub fn yank_lines(&mut self, count: usize) {
+        let Some(view) = self.windows[self.window_focus()].view
+        else {
+            return;
+        };
+        let line = self.windows[self.window_focus()].cursor.y as usize;
+        let text = &self.buffers[view.buffer].text;
+        let last = line.saturating_add(count).min(text.line_count()) - 1;
+        let yanked =
+            text.slice(text.byte_offset_of(line, 0)..text.byte_offset_of(last, usize::MAX));
+        self.yank(yanked, true);
+    }
+
+    // Inserts the text of the selected register `count` times, after the cursor or before it.
+    // The cursor ends up on the last inserted character. Linewise text is inserted as whole lines
+    // below the cursor line or above it instead.
+    pub fn paste(&mut self, count: usize, before: bool) {
+        let name = self.register.to_ascii_lowercase();
+        let Some(register) = self.registers.get(&name).filter(|register| !register.text.is_empty())
+        else {
+            self.emit_message(format!("Nothing in register {name}"));
+            return;
+        };
+        let linewise = register.linewise;
+        let pasted = if linewise {
+            format!("{}\n", register.text).repeat(count)
+        }
+        else {
+            register.text.repeat(count)
+        };
+        let Some((buffer, mut offset)) = self.cursor_edit_target()
+        else {
+            return;
+        };
+        self.last_change = Some(Change::Paste(self.register, count, before));
+        if linewise {
+            self.paste_lines(buffer, pasted, before);
+            return;
+        }
+        let text = &mut self.buffers[buffer].text;
+        if let Some(character) = text.char_at(offset).filter(|&c| !before && c != '\n') {
+            offset += character.len_utf8();
+        }
+        text.insert(offset, &pasted);
+        let last = pasted.chars().next_back().map_or(0, char::len_utf8);
+        let (line, column) = text.position_of(offset + pasted.len() - last);
+        let id = self.window_focus();
+        self.windows[id].cursor = Position { x: column as u16, y: line as u16 };
+        self.clamp_cursor(id);
+        self.redraw_buffer(buffer);
+    }
+
+    // Inserts the lines below the cursor line, or above it, and moves the cursor to the first
+    // non-blank character of the first inserted line.
+    fn paste_lines(&mut self, buffer: BufferID, mut pasted: String, before: bool) {
+        let id = self.window_focus();
+        let line = self.windows[id].cursor.y as usize + usize::from(!before);
+        let text = &mut self.buffers[buffer].text;
+        let offset = if line < text.line_count() {
+            text.byte_offset_of(line, 0)
+        }
+        else {
+            // Below the last line, the lines follow a new line break instead of ending with one.
+            pasted.pop();
+            pasted.insert(0, '\n');
+            text.len()
+        };
+        text.insert(offset, &pasted);
+        let column = text.line(line).chars().take_while(|c| c.is_whitespace()).count();
+        self.windows[id].cursor = Position { x: column as u16, y: line as u16 };
+        self.clamp_cursor(id);
+        self.redraw_buffer(buffer);
+    }
+
+    // Yanks the columns of the cursor line into the register, and removes them unless yanking.
+    // Returns whether the operator was applied.
+    fn apply_to_columns(
+        &mut self,
+        operator: Operator,
+        line: &[char],
+        columns: Range<usize>,
+    ) -> bool {
+        let id = self.window_focus();
+        let cursor = self.windows[id].cursor;
+        let yanked: String = line[columns.clone()].iter().collect();
+        let width = yanked.len();
+        let buffer = match operator {
+            Operator::Yank => None,
+            _ => match self.cursor_edit_target() {
+                Some((buffer, _)) => Some(buffer),
+                None => return false,
+            },
+        };
+        self.yank(yanked, false);
+        if let Some(buffer) = buffer {
+            let text = &mut self.buffers[buffer].text;
+            let start = text.byte_offset_of(cursor.y as usize, columns.start);
+            text.remove(start, width);
+            self.redraw_buffer(buffer);
+        }
+        self.windows[id].cursor.x = columns.start as u16;
+        if operator == Operator::Change {
+            self.continue_in_insert();
+        }
+        self.clamp_cursor(id);
+        true
+    }
+
+    // Replaces `count` lines starting from the cursor line with an empty line, in insert mode.
+    pub fn change_lines(&mut self, count: usize) {
+        let Some((buffer, _)) = self.cursor_edit_target()
+        else {
+            return;
+        };
+        let id = self.window_focus();
+        let line = self.windows[id].cursor.y as usize;
+        let text = &mut self.buffers[buffer].text;
+        let start = text.byte_offset_of(line, 0);
+        let last = line.saturating_add(count).min(text.line_count()) - 1;
+        let end = text.byte_offset_of(last, usize::MAX);
+        let yanked = text.slice(start..end);
+        text.remove(start, end - start);
+        self.yank(yanked, true);
+        self.windows[id].cursor.x = 0;
+        self.continue_in_insert();
+        self.insert_change = Some(Change::EmptyLines(count));
+        self.clamp_cursor(id);
+        self.redraw_buffer(buffer);
+    }
+
+    // Repeats the last change, with `count` replacing the count it was made with.
+    pub fn repeat_change(&mut self, count: Option<usize>) {
+        match self.last_change.clone() {
+            Some(Change::DeleteChars(n)) => self.delete_chars(count.unwrap_or(n)),
+            Some(Change::DeleteLines(n)) => self.delete_lines(count.unwrap_or(n)),
+            Some(Change::JoinLines(n)) => self.join_lines(count.unwrap_or(n)),
+            Some(Change::Object(operator, object, around)) => {
+                self.apply_to_text_object(operator, object, around)
+            }
+            Some(Change::Motion(operator, motion, n)) => {
+                self.apply_to_motion(operator, motion, count.unwrap_or(n))
+            }
+            Some(Change::EmptyLines(n)) => self.change_lines(count.unwrap_or(n)),
+            Some(Change::ShiftLines(n, indent)) => self.shift_lines(count.unwrap_or(n), indent),
+            Some(Change::ToggleCase(n)) => self.toggle_case(count.unwrap_or(n)),
+            Some(Change::ReplaceChars(character, n)) => {
+                self.replace_chars(character, count.unwrap_or(n))
+            }
+            Some(Change::Paste(register, n, before)) => {
+                let selected = std::mem::replace(&mut self.register, register);
+                self.paste(count.unwrap_or(n), before);
+                self.register = selected;
+            }
+            Some(Change::Insert(text)) => {
+                self.mode = Mode::Insert;
+                self.repeat_insertion(&text, count.unwrap_or(1));
+                self.set_mode(Mode::Normal);
+            }
+            // The count applies to the change, and the text is inserted once.
+            Some(Change::InsertAfter(change, text)) => {
+                let last_change = self.last_change.replace(*change);
+                self.repeat_change(count);
+                if self.mode == Mode::Insert {
+                    self.repeat_insertion(&text, 1);
+                    self.insert_change = None;
+                    self.set_mode(Mode::Normal);
+                }
+                self.last_change = last_change;
+            }
+            None => {}
+        }
+    }
+
+    fn repeat_insertion(&mut self, text: &str, count: usize) {
+        for character in text.chars().cycle().take(text.chars().count() * count) {
+            match self.cursor_edit_target() {
+                Some((buffer, offset)) => self.insert_at_cursor(buffer, offset, character),
+                None => break,
+            }
+        }
+    }
+
+    // Removes the character before the cursor, joining lines at the start of a line.
+    pub fn delete_char_backward(&mut self) {
+        let Some((buffer, offset)) = self.cursor_edit_target()
+        else {
+            return;
+        };
+        let window = &mut self.windows[self.tabs[self.current_tab].window_focus];
+        let text = &mut self.buffers[buffer].text;
+        let start = if window.cursor.x != 0 {
+            window.cursor.x -= 1;
+            text.byte_offset_of(window.cursor.y as usize, window.cursor.x as usize)
+        }
+        else if window.cursor.y != 0 {
+            let (line, column) = text.position_of(offset - 1);
+            window.cursor = Position { x: column as u16, y: line as u16 };
+            offset - 1
+        }
+        else {
+            return;
+        };
+        text.remove(start, offset - start);
+        self.clamp_cursor(self.window_focus());
+        self.redraw_buffer(buffer);
+        if self.mode == Mode::Insert {
+            self.insertion.pop();
+        }
+    }
+
+    pub fn begin_replace(&mut self) {
+        self.overwritten.clear();
+        self.mode = Mode::Replace;
+    }
+
+    // Overwrites the character under the cursor, remembering it so that it can be restored.
+    pub fn overwrite_char(&mut self, character: char) {
+        let Some((buffer, offset)) = self.cursor_edit_target()
+        else {
+            return;
+        };
+        let text = &mut self.buffers[buffer].text;
+        let old = text.char_at(offset).filter(|&old| old != '\n' && character != '\n');
+        if let Some(old) = old {
+            text.remove(offset, old.len_utf8());
+        }
+        self.overwritten.push(old);
+        self.insert_at_cursor(buffer, offset, character);
+    }
+
+    // Undoes the most recent overwrite, or just moves the cursor left if there is none.
+    pub fn restore_overwritten_char(&mut self) {
+        match self.overwritten.pop() {
+            Some(old) => {
+                self.delete_char_backward();
+                if let (Some(old), Some((buffer, offset))) = (old, self.cursor_edit_target()) {
+                    self.buffers[buffer].text.insert(offset, old.encode_utf8(&mut [0; 4]));
+                    self.redraw_buffer(buffer);
+                }
+            }
+            None => self.move_cursor(util::Direction::Left),
+        }
+    }
+
+    // The pattern whose matches are highlighted: the one being typed with `incsearch`, or else
+    // the one of the last search.
+    pub fn highlighted_pattern(&self) -> Option<search::Pattern<'_>> {
+        let text = (self.search_preview.as_ref().map(|preview| preview.pattern.as_str()))
+            .or(self.search.as_deref().filter(|_| self.search_highlight))?;
+        self.compile_pattern(text).ok()
+    }
+
+    // Compiles the pattern with the search settings, or describes why it is invalid.
+    fn compile_pattern<'a>(&self, text: &'a str) -> Result<search::Pattern<'a>, String> {
+        let settings = &self.settings;
+        search::Pattern::new(text, settings.ignorecase, settings.smartcase, settings.regex)
+    }
+
+    // Searches for `pattern`, or for the last pattern if it is empty.
+    pub fn search(&mut self, pattern: String, backward: bool) {
+        if !pattern.is_empty() {
+            self.search = Some(pattern);
+        }
+        self.search_backward = backward;
+        self.search_next(false);
+    }
+
+    // The position of the match of the pattern after the cursor of the window, or before it if
+    // `backward`, wrapping around the ends of the buffer, and whether it wrapped.
+    fn find_match(
+        &self,
+        id: WindowID,
+        matcher: &search::Pattern,
+        backward: bool,
+    ) -> Option<(Position, bool)> {
+        let window = &self.windows[id];
+        let text = self.buffers[window.view?.buffer].text.gather();
+        let lines: Vec<&str> = text.split('\n').collect();
+        let y = window.cursor.y as usize;
+        let x = (lines[y].char_indices().nth(window.cursor.x as usize))
+            .map_or(lines[y].len(), |(x, _)| x);
+        let count = lines.len();
+        // The cursor line is searched first from the cursor, and last as a whole.
+        (0..=count).find_map(|step| {
+            let line =
+                if backward { (y + count - step % count) % count } else { (y + step) % count };
+            let mut starts = matcher.find_all(lines[line]).into_iter().map(|range| range.start);
+            let start = match (backward, step) {
+                (false, 0) => starts.find(|&start| start > x),
+                (true, 0) => starts.rfind(|&start| start < x),
+                (false, _) => starts.next(),
+                (true, _) => starts.next_back(),
+            };
+            let wrapped = if backward { step > y } else { y + step >= count };
+            let column = |start| lines[line][..start].chars().count() as u16;
+            start.map(|start| (Position { x: column(start), y: line as u16 }, wrapped))
+        })
+    }
+
+    // Searches for the word under or after the cursor on the cursor line, as a whole word.
+    pub fn search_word(&mut self, backward: bool) {
+        let id = self.window_focus();
+        let Some(view) = self.windows[id].view
+        else {
+            return;
+        };
+        let cursor = self.windows[id].cursor;
+        let line: Vec<char> =
+            self.buffers[view.buffer].text.line(cursor.y as usize).chars().collect();
+        let is_word = |x: &usize| search::is_word_char(line[*x]);
+        let Some(found) = (cursor.x as usize..line.len()).find(is_word)
+        else {
+            self.emit_message(String::from("No identifier under cursor"));
+            return;
+        };
+        let start = (0..found).rev().take_while(is_word).last().unwrap_or(found);
+        let end = (found..line.len()).find(|x| !is_word(x)).unwrap_or(line.len());
+        let word: String = line[start..end].iter().collect();
+        // Searching from the start of the word skips over the word itself.
+        self.windows[id].cursor.x = start as u16;
+        let word = if self.settings.regex { regex::escape(&word) } else { word };
+        self.search(format!("\\<{word}\\>"), backward);
+    }
+
+    // Moves the cursor to the next match of the last search, or the previous one if `reverse`.
+    pub fn search_next(&mut self, reverse: bool) {
+        let Some(pattern) = self.search.clone()
+        else {
+            self.emit_message(String::from("No previous search pattern"));
+            return;
+        };
+        let id = self.window_focus();
+        let backward = self.search_backward != reverse;
+        let matcher = match self.compile_pattern(&pattern) {
+            Ok(matcher) => matcher,
+            Err(error) => {
+                self.emit_message(error);
+                return;
+            }
+        };
+        let Some((position, wrapped)) = self.find_match(id, &matcher, backward)
+        else {
+            self.emit_message(format!("Pattern not found: {pattern}"));
+            return;
+        };
+        self.push_jump();
+        if wrapped {
+            self.emit_message(String::from(if backward {
+                "search hit TOP, continuing at BOTTOM"
+            }
+            else {
+                "search hit BOTTOM, continuing at TOP"
+            }));
+        }
+        self.windows[id].cursor = position;
+        self.clamp_cursor(id);
+        self.search_highlight = true;
+        self.force_redraw();
+    }
+
+    // Remembers where the cursor was before a search, if searches are previewed as they are typed.
+    pub fn begin_search_preview(&mut self) {
+        let window = &self.windows[self.window_focus()];
+        if let (true, Some(_)) = (self.settings.incsearch, window.view) {
+            let (pattern, scroll) = (String::new(), (window.scroll_x, window.scroll_y));
+            self.search_preview = Some(SearchPreview { pattern, cursor: window.cursor, scroll });
+        }
+    }
+
+    // Moves the cursor to the first match of the partial pattern, from where the search began.
+    pub fn preview_search(&mut self, pattern: &str, backward: bool) {
+        let Some(preview) = &mut self.search_preview
+        else {
+            return;
+        };
+        if preview.pattern == pattern {
+            return;
+        }
+        pattern.clone_into(&mut preview.pattern);
+        let (cursor, scroll) = (preview.cursor, preview.scroll);
+        let id = self.window_focus();
+        let window = &mut self.windows[id];
+        (window.cursor, (window.scroll_x, window.scroll_y)) = (cursor, scroll);
+        let found = (self.compile_pattern(pattern).ok())
+            .and_then(|matcher| self.find_match(id, &matcher, backward));
+        if let Some((position, _)) = found {
+            self.windows[id].cursor = position;
+        }
+        self.clamp_cursor(id);
+        self.force_redraw();
+    }
+
+    // Puts the cursor back where it was before the search was previewed.
+    pub fn end_search_preview(&mut self) {
+        if let Some(SearchPreview { cursor, scroll, .. }) = self.search_preview.take() {
+            let id = self.window_focus();
+            let window = &mut self.windows[id];
+            (window.cursor, (window.scroll_x, window.scroll_y)) = (cursor, scroll);
+            self.force_redraw();
+        }
+    }
+
+    // Moves the cursor to the bracket that matches the first bracket at or after the cursor on
+    // the cursor line, counting nested pairs.
+    pub fn match_bracket(&mut self) {
+        let id = self.window_focus();
+        let Some(view) = self.windows[id].view
+        else {
+            return;
+        };
+        let cursor = self.windows[id].cursor;
+        let text = &self.buffers[view.buffer].text;
+        let offset = text.byte_offset_of(cursor.y as usize, cursor.x as usize);
+        let string = text.gather();
+        let line_end = string[offset..].find('\n').map_or(string.len(), |end| offset + end);
+        let Some((start, (open, close, forward))) =
+            string[offset..line_end].char_indices().find_map(|(index, character)| {
+                let pair = match character {
+                    '(' | ')' => ('(', ')', character == '('),
+                    '[' | ']' => ('[', ']', character == '['),
+                    '{' | '}' => ('{', '}', character == '{'),
+                    _ => return None,
+                };
+                Some((offset + index, pair))
+            })
+        else {
+            self.emit_message(String::from("No bracket on the line"));
+            return;
+        };
+        let mut depth = 0;
+        let mut is_match = |(_, character): &(usize, char)| {
+            match character {
+                _ if *character == open => depth += 1,
+                _ if *character == close => depth -= 1,
+                _ => return false,
+            }
+            depth == 0
+        };
+        let found = if forward {
+            string[start..].char_indices().find(&mut is_match).map(|(index, _)| start + index)
+        }
+        else {
+            let end = start + close.len_utf8();
+            string[..end].char_indices().rev().find(&mut is_match).map(|(index, _)| index)
+        };
+        match found {
+            Some(found) => {
+                let (line, column) = text.position_of(found);
+                self.push_jump();
+                self.windows[id].cursor = Position { x: column as u16, y: line as u16 };
+                self.clamp_cursor(id);
+            }
+            None => self.emit_message(String::from("No matching bracket")),
+        }
+    }
+
+    pub fn set_mark(&mut self, name: char) {
+        let window = &self.windows[self.window_focus()];
+        if let Some(view) = window.view {
+            self.marks.insert(name, (view.buffer, window.cursor));
+        }
+    }
+
+    // Moves the cursor to the mark, showing its buffer if needed.
+    pub fn jump_to_mark(&mut self, name: char) {
+        match self.marks.get(&name).copied() {
+            Some((buffer, position)) if self.buffers.get(buffer).is_some() => {
+                self.push_jump();
+                self.go_to(buffer, position);
+            }
+            _ => self.emit_message(String::from("Mark not set")),
+        }
+    }
+
+    // Shows the position in the focused window. The position is clamped, since the text may have
+    // changed after it was recorded.
+    fn go_to(&mut self, buffer: BufferID, position: Position) {
+        let id = self.window_focus();
+        if self.windows[id].view.is_none_or(|view| view.buffer != buffer) {
+            self.show_buffer(id, buffer);
+        }
+        self.windows[id].cursor = position;
+        self.clamp_cursor(id);
+    }
+
+    // Records the cursor position before a jump, so that `Ctrl-o` can return to it. Positions
+    // newer than the current one in the jump list are forgotten.
+    fn push_jump(&mut self) {
+        let window = &self.windows[self.window_focus()];
+        let Some(view) = window.view
+        else {
+            return;
+        };
+        let (buffer, cursor) = (view.buffer, window.cursor);
+        self.jumps.truncate(self.jump_index);
+        self.jumps.retain(|&(other, position)| other != buffer || position.y != cursor.y);
+        self.jumps.push((buffer, cursor));
+        if self.jumps.len() > JUMP_LIST_CAPACITY {
+            self.jumps.remove(0);
+        }
+        self.jump_index = self.jumps.len();
+    }
+
+    pub fn jump_back(&mut self) {
+        if self.jump_index == self.jumps.len() {
+            // Remember where the jumps back started, so that `jump_forward` can return there.
+            self.push_jump();
+            if self.jumps.is_empty() {
+                return;
+            }
+            self.jump_index = self.jumps.len() - 1;
+        }
+        self.jump_index = self.jump_index.saturating_sub(1);
+        self.go_to_jump();
+    }
+
+    pub fn jump_forward(&mut self) {
+        if self.jump_index + 1 < self.jumps.len() {
+            self.jump_index += 1;
+            self.go_to_jump();
+        }
+    }
+
+    fn go_to_jump(&mut self) {
+        if let Some(&(buffer, position)) = self.jumps.get(self.jump_index) {
+            if self.buffers.get(buffer).is_some() {
+                self.go_to(buffer, position);
+            }
+        }
+    }
+
+    // Moves the cursor to the first non-blank character of the line, counted from zero.
+    pub fn goto_line(&mut self, line: usize) {
+        let id = self.window_focus();
+        let Some(view) = self.windows[id].view
+        else {
+            return;
+        };
+        let buffer = &self.buffers[view.buffer];
+        let (text, line) = (&buffer.text, line.min(buffer.lines().max(1) - 1));
+        let start = text.byte_offset_of(line, 0);
+        let mut indentation = 0;
+        while matches!(text.char_at(start + indentation), Some(' ' | '\t')) {
+            indentation += 1;
+        }
+        self.push_jump();
+        self.go_to(view.buffer, Position { x: indentation as u16, y: line as u16 });
+    }
+
+    pub fn find_char(&mut self, find: FindChar, count: usize) {
+        self.last_find = Some(find);
+        self.move_to_char(find, count, false);
+    }
+
+    // Repeats the last find, in the opposite direction if `reverse`.
+    pub fn repeat_find_char(&mut self, reverse: bool, count: usize) {
+        if let Some(find) = self.last_find {
+            self.move_to_char(FindChar { backward: find.backward != reverse, ..find }, count, true);
+        }
+    }
+
+    // Moves to the `count`th occurrence of the character. A repeated `t` motion skips over the
+    // occurrence right next to the cursor, since it would not move otherwise.
+    fn move_to_char(&mut self, find: FindChar, count: usize, repeat: bool) {
+        let id = self.window_focus();
+        let Some(view) = self.windows[id].view
+        else {
+            return;
+        };
+        let cursor = self.windows[id].cursor;
+        let line: Vec<char> =
+            self.buffers[view.buffer].text.line(cursor.y as usize).chars().collect();
+        let (x, skip, till) = (cursor.x as usize, 1 + usize::from(find.till && repeat), find.till);
+        let is_match = |column: &usize| line[*column] == find.character;
+        let found = if find.backward {
+            let mut columns = (0..x.saturating_sub(skip - 1)).rev().filter(is_match);
+            columns.nth(count.max(1) - 1).map(|column| column + usize::from(till))
+        }
+        else {
+            let mut columns = (x + skip..line.len()).filter(is_match);
+            columns.nth(count.max(1) - 1).map(|column| column - usize::from(till))
+        };
+        if let Some(column) = found {
+            self.windows[id].cursor.x = column as u16;
+            self.clamp_cursor(id);
+        }
+    }
+
+    // Moves the cursor through the text. Moving up and down keeps the screen column the cursor had
+    // before, and with `whichwrap`, moving left and right continues on the adjacent lines.
+    pub fn move_cursor(&mut self, direction: util::Direction) {
+        let id = self.window_focus();
+        let Window { cursor, view, sticky_column, .. } = self.windows[id];
+        let Some(view) = view
+        else {
+            return;
+        };
+        let last_line = self.last_cursor_line(view.buffer);
+        let text = &self.buffers[view.buffer].text;
+        let (line, column) = (cursor.y as usize, cursor.x as usize);
+        let last_column = match self.mode {
+            Mode::Insert | Mode::Replace => text.line_length(line),
+            _ => text.line_length(line).saturating_sub(1),
+        };
+        let whichwrap = self.settings.whichwrap;
+        let target = match direction {
+            util::Direction::Up | util::Direction::Down => {
+                let target = match direction {
+                    util::Direction::Up => line.checked_sub(1),
+                    _ => (line < last_line).then_some(line + 1),
+                };
+                let Some(target) = target
+                else {
+                    return;
+                };
+                let screen_column = match sticky_column {
+                    Some((screen_column, position)) if position == cursor => screen_column,
+                    _ => self.display_column(view.buffer, line, column),
+                };
+                let column = self.column_at_display(view.buffer, target, screen_column);
+                self.windows[id].cursor = Position { x: column as u16, y: target as u16 };
+                self.clamp_cursor(id);
+                self.windows[id].sticky_column = Some((screen_column, self.windows[id].cursor));
+                self.remember_cursor(id);
+                return;
+            }
+            util::Direction::Left if column > 0 => Position { x: cursor.x - 1, y: cursor.y },
+            util::Direction::Left if whichwrap && line > 0 => {
+                Position { x: u16::MAX, y: cursor.y - 1 }
+            }
+            util::Direction::Right if column < last_column => cursor.offset_x(1),
+            util::Direction::Right if whichwrap && line < last_line => {
+                Position { x: 0, y: cursor.y + 1 }
+            }
+            _ => return,
+        };
+        self.windows[id].cursor = target;
+        self.clamp_cursor(id);
+        self.remember_cursor(id);
+    }
+
+    // The open windows of the current tab in reading order, top to bottom and left to right.
+    fn windows_in_layout_order(&self) -> Vec<WindowID> {
+        let mut ids = self.tabs[self.current_tab].open_windows.clone();
+        ids.sort_by_key(|&id| (self.windows[id].position.y, self.windows[id].position.x));
+        ids
+    }
+
+    pub fn rotate_focus_forward(&mut self) {
+        let ids = self.windows_in_layout_order();
+        if let Some(index) = ids.iter().position(|&id| id == self.window_focus()) {
+            self.set_window_focus(ids[util::rotate_forward(0, ids.len(), index)]);
+        }
+    }
+
+    pub fn rotate_focus_backward(&mut self) {
+        let ids = self.windows_in_layout_order();
+        if let Some(index) = ids.iter().position(|&id| id == self.window_focus()) {
+            self.set_window_focus(ids[util::rotate_backward(0, ids.len(), index)]);
+        }
+    }
+
+    fn run_cursor_focus_beam(&self, beam: impl Iterator<Item = Position>) -> Option<WindowID> {
+        let window_focus = self.window_focus();
+        beam.flat_map(|position| {
+            self.tabs[self.current_tab]
+                .open_windows
+                .iter()
+                .copied()
+                .filter(move |&id| id != window_focus && self.windows[id].contains(position))
+        })
+        .next()
+    }
+
+    fn send_cursor_focus_beam(&self, direction: util::Direction) -> Option<WindowID> {
+        let window = &self.windows[self.tabs[self.current_tab].window_focus];
+        let cursor = self.screen_cursor(self.window_focus());
+
+        match direction {
+            util::Direction::Up => {
+                let range = 0..window.position.y;
+                self.run_cursor_focus_beam(range.rev().map(|y| Position { x: cursor.x, y }))
+            }
+            util::Direction::Down => {
+                let range = window.position.y..self.size.height;
+                self.run_cursor_focus_beam(range.map(|y| Position { x: cursor.x, y }))
+            }
+            util::Direction::Left => {
+                let range = 0..window.position.x;
+                self.run_cursor_focus_beam(range.rev().map(|x| Position { x, y: cursor.y }))
+            }
+            util::Direction::Right => {
+                let range = window.position.x + window.size.width..self.size.width;
+                self.run_cursor_focus_beam(range.map(|x| Position { x, y: cursor.y }))
+            }
+        }
+    }
+
+    pub fn move_focus(&mut self, direction: util::Direction) {
+        self.send_cursor_focus_beam(direction).inspect(|&id| self.set_window_focus(id));
+    }
+
+    // The windows that together cover the whole edge of `area` facing the given direction.
+    fn windows_along_edge(&self, area: &Window, direction: util::Direction) -> Vec<WindowID> {
+        let (left, top) = (area.position.x, area.position.y);
+        let (right, bottom) = (left + area.size.width, top + area.size.height);
+        let ids: Vec<WindowID> = self.tabs[self.current_tab]
+            .open_windows
+            .iter()
+            .copied()
+            .filter(|&id| {
+                let window = &self.windows[id];
+                let (window_left, window_top) = (window.position.x, window.position.y);
+                let window_right = window_left + window.size.width;
+                let window_bottom = window_top + window.size.height;
+                let within_rows = top <= window_top && window_bottom <= bottom;
+                let within_columns = left <= window_left && window_right <= right;
+                match direction {
+                    util::Direction::Left => window_right == left && within_rows,
+                    util::Direction::Right => window_left == right && within_rows,
+                    util::Direction::Up => window_bottom == top && within_columns,
+                    util::Direction::Down => window_top == bottom && within_columns,
+                }
+            })
+            .collect();
+        let covered: u16 = ids
+            .iter()
+            .map(|&id| match direction {
+                util::Direction::Left | util::Direction::Right => self.windows[id].size.height,
+                util::Direction::Up | util::Direction::Down => self.windows[id].size.width,
+            })
+            .sum();
+        let edge = match direction {
+            util::Direction::Left | util::Direction::Right => area.size.height,
+            util::Direction::Up | util::Direction::Down => area.size.width,
+        };
+        if covered == edge {
+            ids
+        }
+        else {
+            Vec::new()
+        }
+    }
+
+    // Closes the focused window, and gives its area to the windows along one of its edges.
+    pub fn close_window(&mut self) {
+        let tab = &self.tabs[self.current_tab];
+        if tab.open_windows.len() == 1 {
+            if self.tabs.len() == 1 {
+                self.emit_message(String::from("Cannot close last window"));
+            }
+            else {
+                self.tab_close();
+            }
+            return;
+        }
+        let id = tab.window_focus;
+        self.windows[id].is_open = false;
+        let focus = self.detach_window(id);
+        self.set_window_focus(focus.unwrap_or(self.tabs[self.current_tab].open_windows[0]));
+    }
+
+    // Removes the window from the tab and gives its area to the windows along one of its edges,
+    // returning the first of them.
+    fn detach_window(&mut self, id: WindowID) -> Option<WindowID> {
+        let closed = self.windows[id];
+        self.tabs[self.current_tab].open_windows.retain(|&open| open != id);
+        let directions = [
+            util::Direction::Left,
+            util::Direction::Up,
+            util::Direction::Right,
+            util::Direction::Down,
+        ];
+        let (direction, neighbors) = directions
+            .into_iter()
+            .map(|direction| (direction, self.windows_along_edge(&closed, direction)))
+            .find(|(_, neighbors)| !neighbors.is_empty())
+            .unwrap_or((util::Direction::Left, Vec::new()));
+        for &neighbor in &neighbors {
+            let Window { mut position, mut size, .. } = self.windows[neighbor];
+            match direction {
+                util::Direction::Left => size.width += closed.size.width,
+                util::Direction::Up => size.height += closed.size.height,
+                util::Direction::Right => {
+                    position.x = closed.position.x;
+                    size.width += closed.size.width;
+                }
+                util::Direction::Down => {
+                    position.y = closed.position.y;
+                    size.height += closed.size.height;
+                }
+            }
+            self.set_window_area(neighbor, position, size);
+        }
+        neighbors.first().copied()
+    }
+
+    // Moves the focused window to the given edge of the tab, spanning all of it. The window keeps
+    // its extent across the edge, up to half of the tab, and the other windows fill the rest.
+    pub fn move_window_to_edge(&mut self, direction: util::Direction) {
+        let id = self.window_focus();
+        if self.tabs[self.current_tab].open_windows.len() == 1 {
+            return;
+        }
+        let window = self.windows[id];
+        self.detach_window(id);
+        let layout = self.layout_size();
+        let vertical = matches!(direction, util::Direction::Left | util::Direction::Right);
+        let (extent, length) = if vertical {
+            (window.size.width, layout.width)
+        }
+        else {
+            (window.size.height, layout.height)
+        };
+        let extent = extent.min(length / 2);
+        let rest = length - extent;
+        // The start of the window and of the area left for the other windows.
+        let (start, offset) = match direction {
+            util::Direction::Left | util::Direction::Up => (0, extent),
+            util::Direction::Right | util::Direction::Down => (rest, 0),
+        };
+        for other in self.tabs[self.current_tab].open_windows.clone() {
+            let Window { mut position, mut size, .. } = self.windows[other];
+            if vertical {
+                let right = offset + util::rescale(position.x + size.width, length, rest);
+                position.x = offset + util::rescale(position.x, length, rest);
+                size.width = right - position.x;
+            }
+            else {
+                let bottom = offset + util::rescale(position.y + size.height, length, rest);
+                position.y = offset + util::rescale(position.y, length, rest);
+                size.height = bottom - position.y;
+            }
+            self.set_window_area(other, position, size);
+        }
+        let (position, size) = if vertical {
+            (Position { x: start, y: 0 }, Size { width: extent, ..layout })
+        }
+        else {
+            (Position { x: 0, y: start }, Size { height: extent, ..layout })
+        };
+        self.tabs[self.current_tab].open_windows.push(id);
+        self.set_window_area(id, position, size);
+    }
+
+    // Closes every window of the tab except the focused one, which then fills the tab.
+    pub fn only_window(&mut self) {
+        let id = self.window_focus();
+        let tab = &mut self.tabs[self.current_tab];
+        for &other in &tab.open_windows {
+            if other != id {
+                self.windows[other].is_open = false;
+            }
+        }
+        tab.open_windows = vec![id];
+        self.set_window_area(id, Position::default(), self.layout_size());
+    }
+
+    // Exchanges the areas of the focused window and the first window found beside it.
+    pub fn swap_window(&mut self) {
+        let directions = [
+            util::Direction::Right,
+            util::Direction::Down,
+            util::Direction::Left,
+            util::Direction::Up,
+        ];
+        let Some(other) =
+            directions.into_iter().find_map(|direction| self.send_cursor_focus_beam(direction))
+        else {
+            self.emit_message(String::from("No window to swap with"));
+            return;
+        };
+        let id = self.window_focus();
+        let (Window { position, size, .. }, other_window) = (self.windows[id], self.windows[other]);
+        self.set_window_area(id, other_window.position, other_window.size);
+        self.set_window_area(other, position, size);
+    }
+
+    // The windows that end and start at the line `line` across the axis, along the stretch of the
+    // line that contains `span`, up to where the line crosses another.
+    fn windows_beside_line(
+        &self,
+        line: u16,
+        span: (u16, u16),
+        vertical: bool,
+    ) -> (Vec<WindowID>, Vec<WindowID>) {
+        let (mut from, mut to) = (span.0, span.0 + span.1);
+        let (mut before, mut after) = (Vec::new(), Vec::new());
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &id in &self.tabs[self.current_tab].open_windows {
+                let (start, length) = window_extent(&self.windows[id], vertical);
+                let (along, along_length) = window_extent(&self.windows[id], !vertical);
+                let side = if start + length == line {
+                    &mut before
+                }
+                else if start == line {
+                    &mut after
+                }
+                else {
+                    continue;
+                };
+                if along < to && from < along + along_length && !side.contains(&id) {
+                    side.push(id);
+                    (from, to) = (from.min(along), to.max(along + along_length));
+                    changed = true;
+                }
+            }
+        }
+        (before, after)
+    }
+
+    // Grows the focused window by `delta` columns if `vertical`, or rows otherwise, moving its far
+    // edge, or its near edge at the edge of the tab. Shrinks it if `delta` is negative.
+    pub fn resize_window(&mut self, vertical: bool, delta: i32) {
+        let window = self.windows[self.window_focus()];
+        let (start, length) = window_extent(&window, vertical);
+        let span = window_extent(&window, !vertical);
+        let (before, after, shift) = match self.windows_beside_line(start + length, span, vertical)
+        {
+            (before, after) if !after.is_empty() => (before, after, delta),
+            _ => match self.windows_beside_line(start, span, vertical) {
+                (before, after) if !before.is_empty() => (before, after, -delta),
+                _ => return,
+            },
+        };
+        // Windows are not shrunk below the size needed to split them.
+        let shrunk = if shift < 0 { &before } else { &after };
+        if shrunk
+            .iter()
+            .any(|&id| (window_extent(&self.windows[id], vertical).1 as i32) - shift.abs() < 6)
+        {
+            return;
+        }
+        for (ids, moved) in [(before, false), (after, true)] {
+            for id in ids {
+                let Window { mut position, mut size, .. } = self.windows[id];
+                let (start, length) = if vertical {
+                    (&mut position.x, &mut size.width)
+                }
+                else {
+                    (&mut position.y, &mut size.height)
+                };
+                if moved {
+                    *start = (*start as i32 + shift) as u16;
+                    *length = (*length as i32 - shift) as u16;
+                }
+                else {
+                    *length = (*length as i32 + shift) as u16;
+                }
+                self.set_window_area(id, position, size);
+            }
+        }
+    }
+
+    // Groups the windows by the cuts along one axis that no window crosses, in order along it.
+    fn cut_windows(
+        &self,
+        ids: &[WindowID],
+        extent: impl Fn(&Window) -> (u16, u16),
+    ) -> Option<Vec<Vec<WindowID>>> {
+        let mut ids = ids.to_vec();
+        ids.sort_by_key(|&id| extent(&self.windows[id]).0);
+        let mut groups: Vec<Vec<WindowID>> = Vec::new();
+        let mut end = 0;
+        for id in ids {
+            let (start, length) = extent(&self.windows[id]);
+            match groups.last_mut() {
+                Some(group) if start < end => group.push(id),
+                _ => groups.push(vec![id]),
+            }
+            end = end.max(start + length);
+        }
+        (groups.len() > 1).then_some(groups)
+    }
+
+    // Divides the area evenly among the groups of windows separated by cuts across it.
+    fn balance_area(&mut self, ids: Vec<WindowID>, position: Position, size: Size) {
+        if let [id] = ids[..] {
+            self.set_window_area(id, position, size);
+            return;
+        }
+        let Some((groups, vertical)) = self
+            .cut_windows(&ids, |window| (window.position.x, window.size.width))
+            .map(|groups| (groups, true))
+            .or_else(|| {
+                self.cut_windows(&ids, |window| (window.position.y, window.size.height))
+                    .map(|groups| (groups, false))
+            })
+        else {
+            return;
+        };
+        let count = groups.len() as u16;
+        let length = if vertical { size.width } else { size.height };
+        for (index, group) in (0..).zip(groups) {
+            let from = util::rescale(index, count, length);
+            let to = util::rescale(index + 1, count, length);
+            let (position, size) = if vertical {
+                (position.offset_x(from), Size { width: to - from, ..size })
+            }
+            else {
+                (position.offset_y(from), Size { height: to - from, ..size })
+            };
+            self.balance_area(group, position, size);
+        }
+    }
+
+    pub fn balance_windows(&mut self) {
+        let ids = self.tabs[self.current_tab].open_windows.clone();
+        self.balance_area(ids, Position::default(), self.layout_size());
+    }
+
+    // Splits the focused window and edits the file in the new window, which gets the focus.
+    pub fn split_and_edit(
+        &mut self,
+        vertical: bool,
+        size: Option<u16>,
+        path: PathBuf,
+    ) -> io::Result<()> {
+        let split = if vertical {
+            self.vertical_split_window(size)
+        }
+        else {
+            self.horizontal_split_window(size)
+        };
+        if let Some(id) = split {
+            self.set_window_focus(id);
+            if let Err(error) = self.edit(path) {
+                self.close_window();
+                return Err(error);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn vertical_split_window(&mut self, width: Option<u16>) -> Option<WindowID> {
+        self.split_window(true, width)
+    }
+
+    pub fn horizontal_split_window(&mut self, height: Option<u16>) -> Option<WindowID> {
+        self.split_window(false, height)
+    }
+
+    // Splits the focused window in half, with the new window to the right or below. The new window
+    // views the same buffer from the same position, and gets the focus with `splitfocus`. Returns
+    // the new window, if the focused window was large enough to split. With `length`, the new
+    // window gets that many columns or rows instead, and both windows must keep at least 3.
+    fn split_window(&mut self, vertical: bool, length: Option<u16>) -> Option<WindowID> {
+        let id = self.window_focus();
+        let Window { position, size, .. } = self.windows[id];
+        if vertical && size.width < 6 {
+            self.emit_message(String::from("The window is too small for a vertical split"));
+            return None;
+        }
+        if !vertical && size.height < 6 {
+            self.emit_message(String::from("The window is too small for a horizontal split"));
+            return None;
+        }
+        let available = if vertical { size.width } else { size.height };
+        let new_length = match length {
+            Some(length) if length < 3 || length > available - 3 => {
+                self.emit_message(format!("Invalid window size: {length}"));
+                return None;
+            }
+            Some(length) => length,
+            None => available - available / 2,
+        };
+        let new_id = self.new_window();
+        self.windows[new_id] = self.windows[id];
+        self.tabs[self.current_tab].open_windows.push(new_id);
+        if vertical {
+            let width = size.width - new_length;
+            self.set_window_area(id, position, Size { width, ..size });
+            let new_size = Size { width: new_length, ..size };
+            self.set_window_area(new_id, position.offset_x(width), new_size);
+        }
+        else {
+            let height = size.height - new_length;
+            self.set_window_area(id, position, Size { height, ..size });
+            let new_size = Size { height: new_length, ..size };
+            self.set_window_area(new_id, position.offset_y(height), new_size);
+        }
+        if self.settings.splitfocus {
+            self.set_window_focus(new_id);
+        }
+        Some(new_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::indexvec::VecIndex;
+    use crate::terminal::{Position, Size};
+
+    fn area(editor: &super::Editor, index: usize) -> (Position, Size) {
+        let window = &editor.windows[editor.tabs[0].open_windows[index]];
+        (window.position, window.size)
+    }
+
+    fn editor_with_text(text: &str) -> super::Editor {
+        let mut editor = super::Editor::new(Size { width: 80, height: 25 });
+        let buffer = editor
+            .buffers
+            .push(super::Buffer { text: text.to_owned().into(), ..super::Buffer::default() });
+        let view = super::View { buffer };
+        let id = editor.window_focus();
+        editor.windows[id].view = Some(view);
+        editor
+    }
+
+    fn text(editor: &super::Editor) -> String {
+        editor.buffers[VecIndex::new(0)].text.gather()
+    }
+
+    #[test]
+    fn repeat_change() {
+        use super::{Mode, Operator};
+        let mut editor = editor_with_text("one\ntwo\nthree\nfour\nfive");
+        editor.delete_chars(2);
+        editor.repeat_change(None);
+        assert_eq!(text(&editor), "\ntwo\nthree\nfour\nfive");
+        editor.delete_lines(1);
+        editor.repeat_change(Some(2));
+        assert_eq!(text(&editor), "four\nfive");
+        editor.begin_insert();
+        "ab".chars().for_each(|character| editor.insert_char(character));
+        editor.delete_char_backward();
+        editor.set_mode(super::Mode::Normal);
+        editor.repeat_change(Some(2));
+        assert_eq!(text(&editor), "aaafour\nfive");
+        editor.move_cursor(crate::util::Direction::Down);
+        editor.delete_lines(3);
+        assert_eq!(text(&editor), "aaafour");
+
+        let mut editor = editor_with_text("one two three\nfour five");
+        let id = editor.window_focus();
+        editor.apply_to_motion(Operator::Change, 'w', 1);
+        editor.insert_char('1');
+        editor.set_mode(Mode::Normal);
+        editor.windows[id].cursor.x = 2;
+        editor.repeat_change(None);
+        assert_eq!(text(&editor), "1 1 three\nfour five");
+        editor.windows[id].cursor = Position { x: 0, y: 1 };
+        editor.repeat_change(Some(2));
+        assert_eq!((text(&editor), editor.mode), ("1 1 three\n1".into(), Mode::Normal));
+        editor.change_lines(1);
+        editor.insert_char('x');
+        editor.set_mode(Mode::Normal);
+        editor.windows[id].cursor.y = 0;
+        editor.repeat_change(None);
+        assert_eq!(text(&editor), "x\nx");
+        editor.apply_to_motion(Operator::Yank, '$', 1);
+        editor.paste(1, false);
+        editor.repeat_change(Some(2));
+        assert_eq!(text(&editor), "xxxx\nx");
+    }
+
+    #[test]
+    fn join_lines() {
+        let mut editor = editor_with_text("a\n  b\n\t\n c\nd");
+        editor.join_lines(1);
+        assert_eq!(text(&editor), "a b\n\t\n c\nd");
+        assert_eq!(editor.windows[editor.window_focus()].cursor.x, 1);
+        editor.join_lines(3);
+        assert_eq!(text(&editor), "a b c\nd");
+        assert_eq!(editor.windows[editor.window_focus()].cursor.x, 3);
+        editor.move_cursor(crate::util::Direction::Down);
+        editor.join_lines(2);
+        assert_eq!(text(&editor), "a b c\nd");
+        assert!(matches!(editor.last_change, Some(super::Change::JoinLines(3))));
+
+        // The final newline is kept when joining at the last line.
+        let mut editor = editor_with_text("a\nb\n");
+        editor.join_lines(5);
+        assert_eq!(text(&editor), "a b\n");
+        editor.last_change = None;
+        editor.join_lines(1);
+        assert_eq!(text(&editor), "a b\n");
+        assert!(editor.last_change.is_none());
+        assert_eq!(editor.buffers[VecIndex::new(0)].text.undo_counts(), (1, 0));
+    }
+
+    #[test]
+    fn insert_newline() {
+        let mut editor = editor_with_text("\t  a");
+        let id = editor.window_focus();
+        editor.begin_insert();
+        editor.windows[id].cursor.x = 4;
+        editor.insert_newline();
+        assert_eq!(text(&editor), "\t  a\n\t  ");
+        editor.windows[id].cursor.x = 1;
+        editor.insert_newline();
+        assert_eq!(text(&editor), "\t  a\n\t\n\t  ");
+        assert_eq!(editor.windows[id].cursor, Position { x: 1, y: 2 });
+    }
+
+    #[test]
+    fn insert_tab() {
+        let mut editor = editor_with_text("a\tb");
+        editor.begin_insert();
+        editor.insert_tab();
+        editor.buffers[VecIndex::new(0)].settings.expandtab = true;
+        editor.buffers[VecIndex::new(0)].settings.tabstop = 4;
+        editor.insert_char('c');
+        editor.insert_tab();
+        editor.move_cursor(crate::util::Direction::Right);
+        editor.move_cursor(crate::util::Direction::Right);
+        editor.insert_tab();
+        assert_eq!(text(&editor), "\tc   a\t    b");
+    }
+
+    #[test]
+    fn move_cursor() {
+        use crate::util::Direction;
+        let mut editor = editor_with_text("abcdef\nab\n\tx\nabcdefghijk");
+        let id = editor.window_focus();
+        let cursor = |editor: &super::Editor| editor.windows[id].cursor;
+        editor.windows[id].cursor.x = 4;
+        editor.move_cursor(Direction::Down);
+        assert_eq!(cursor(&editor), Position { x: 1, y: 1 });
+        editor.move_cursor(Direction::Down);
+        assert_eq!(cursor(&editor), Position { x: 0, y: 2 });
+        editor.move_cursor(Direction::Down);
+        assert_eq!(cursor(&editor), Position { x: 4, y: 3 });
+        editor.move_cursor(Direction::Down);
+        assert_eq!(cursor(&editor), Position { x: 4, y: 3 });
+        editor.move_cursor(Direction::Right);
+        editor.move_cursor(Direction::Up);
+        assert_eq!(cursor(&editor), Position { x: 0, y: 2 });
+        editor.move_cursor(Direction::Up);
+        assert_eq!(cursor(&editor), Position { x: 1, y: 1 });
+        editor.move_cursor(Direction::Right);
+        assert_eq!(cursor(&editor), Position { x: 1, y: 1 });
+        editor.settings.whichwrap = true;
+        editor.move_cursor(Direction::Right);
+        assert_eq!(cursor(&editor), Position { x: 0, y: 2 });
+        editor.move_cursor(Direction::Left);
+        editor.move_cursor(Direction::Left);
+        assert_eq!(cursor(&editor), Position { x: 0, y: 1 });
+        editor.move_cursor(Direction::Left);
+        assert_eq!(cursor(&editor), Position { x: 5, y: 0 });
+
+        // The empty line after a final newline is not a line of the buffer.
+        let mut editor = editor_with_text("a\nb\n");
+        editor.move_cursor(Direction::Down);
+        editor.move_cursor(Direction::Down);
+        assert_eq!(cursor(&editor), Position { x: 0, y: 1 });
+        editor.move_cursor(Direction::Right);
+        assert_eq!(cursor(&editor), Position { x: 0, y: 1 });
+        editor.windows[id].cursor.y = 5;
+        editor.clamp_cursor(id);
+        assert_eq!(cursor(&editor), Position { x: 0, y: 1 });
+        editor.begin_insert_at('A');
+        editor.insert_char('\n');
+        assert_eq!((text(&editor), cursor(&editor)), ("a\nb\n\n".into(), Position { x: 0, y: 2 }));
+    }
+
+    #[test]
+    fn scroll_horizontally() {
+        let mut editor = editor_with_text("0123456789abcdefghij\n\tx");
+        let id = editor.window_focus();
+        editor.windows[id].settings.number = false;
+        editor.windows[id].size.width = 12;
+        let offset = |editor: &mut super::Editor, position| {
+            editor.windows[id].cursor = position;
+            editor.clamp_cursor(id);
+            editor.windows[id].scroll_x
+        };
+        assert_eq!(offset(&mut editor, Position { x: 15, y: 0 }), 6);
+        assert_eq!(offset(&mut editor, Position { x: 10, y: 0 }), 6);
+        assert_eq!(offset(&mut editor, Position { x: 1, y: 0 }), 1);
+        editor.settings.sidescrolloff = 2;
+        assert_eq!(offset(&mut editor, Position { x: 15, y: 0 }), 8);
+        assert_eq!(offset(&mut editor, Position { x: 7, y: 0 }), 5);
+        editor.settings.sidescrolloff = 50;
+        assert_eq!(offset(&mut editor, Position { x: 15, y: 0 }), 10);
+        assert_eq!(offset(&mut editor, Position { x: 1, y: 1 }), 4);
+    }
+
+    #[test]
+    fn screen_cursor() {
+        let mut editor = editor_with_text("\ta\tb");
+        let id = editor.window_focus();
+        editor.windows[id].settings.number = false;
+        let columns: Vec<u16> = (0..4)
+            .map(|_| {
+                let x = editor.screen_cursor(id).x;
+                editor.move_cursor(crate::util::Direction::Right);
+                x
+            })
+            .collect();
+        assert_eq!(columns, [1, 9, 10, 17]);
+    }
+
+    #[test]
+    fn click() {
+        let mut editor = editor_with_text("a\n\tbc\nd");
+        editor.vertical_split_window(None);
+        let left = editor.window_focus();
+        editor.windows[left].settings.number = false;
+        let right = editor.tabs[0].open_windows[1];
+        editor.click(Position { x: 41, y: 1 });
+        assert_eq!(editor.window_focus(), right);
+        editor.click(Position { x: 8, y: 2 });
+        assert_eq!(editor.window_focus(), left);
+        assert_eq!(editor.windows[left].cursor, Position { x: 0, y: 1 });
+        editor.click(Position { x: 9, y: 2 });
+        assert_eq!(editor.windows[left].cursor, Position { x: 1, y: 1 });
+        editor.click(Position { x: 30, y: 20 });
+        assert_eq!(editor.windows[left].cursor, Position { x: 0, y: 2 });
+    }
+
+    #[test]
+    fn scroll_window_at() {
+        let mut editor = editor_with_text(&"\n".repeat(30));
+        let id = editor.window_focus();
+        let position = Position { x: 10, y: 10 };
+        for _ in 0..20 {
+            editor.scroll_window_at(position, crate::util::Direction::Down);
+        }
+        assert_eq!(editor.windows[id].scroll_y, 30);
+        assert_eq!(editor.windows[id].cursor, Position { x: 0, y: 0 });
+        assert!(!editor.cursor_visible(id));
+        editor.scroll_window_at(position, crate::util::Direction::Up);
+        assert_eq!(editor.windows[id].scroll_y, 27);
+        editor.scroll_window_at(Position { x: 10, y: 24 }, crate::util::Direction::Up);
+        assert_eq!(editor.windows[id].scroll_y, 27);
+    }
+
+    #[test]
+    fn tiny_terminal() {
+        let mut editor = super::Editor::new(Size { width: 0, height: 0 });
+        assert!(editor.too_small());
+        editor.horizontal_split_window(None);
+        editor.vertical_split_window(None);
+        editor.resize(Size { width: 80, height: 25 });
+        assert!(!editor.too_small());
+        assert_eq!(area(&editor, 0), (Position { x: 0, y: 0 }, Size { width: 40, height: 24 }));
+        assert_eq!(area(&editor, 1), (Position { x: 40, y: 0 }, Size { width: 40, height: 24 }));
+    }
+
+    #[test]
+    fn laststatus() {
+        let mut editor = super::Editor::new(Size { width: 80, height: 25 });
+        editor.horizontal_split_window(None);
+        editor.set_laststatus(0);
+        assert_eq!(area(&editor, 0), (Position { x: 0, y: 0 }, Size { width: 80, height: 12 }));
+        assert_eq!(area(&editor, 1), (Position { x: 0, y: 12 }, Size { width: 80, height: 13 }));
+        editor.horizontal_split_window(None);
+        editor.set_laststatus(2);
+        assert_eq!(editor.layout_size(), Size { width: 80, height: 24 });
+        let bottom = (0..3)
+            .map(|index| area(&editor, index))
+            .map(|(position, size)| position.y + size.height);
+        let bottom = bottom.max();
+        assert_eq!(bottom, Some(24));
+    }
+
+    #[test]
+    fn line_ending() {
+        use super::LineEnding;
+        assert_eq!(LineEnding::detect("a\nb"), LineEnding::Unix);
+        assert_eq!(LineEnding::detect("a\r\nb\r\nc"), LineEnding::Dos);
+        assert_eq!(LineEnding::detect("a\r\nb\r\nc\n"), LineEnding::Unix);
+        assert_eq!(LineEnding::detect("a\r\nb\nc\n"), LineEnding::Unix);
+
+        let path = std::env::temp_dir().join(format!("tek-line-ending-{}", std::process::id()));
+        std::fs::write(&path, "a\r\nb\r\n").unwrap();
+        let mut buffer = super::Buffer::read(path.clone()).unwrap();
+        assert_eq!((buffer.line_ending, buffer.text.gather()), (LineEnding::Dos, "a\nb\n".into()));
+        buffer.text.insert(1, "\nc");
+        assert_eq!(buffer.write(&path).unwrap(), 9);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "a\r\nc\r\nb\r\n");
+
+        std::fs::write(&path, "a\r\nb\n").unwrap();
+        let buffer = super::Buffer::read(path.clone()).unwrap();
+        assert_eq!(
+            (buffer.line_ending, buffer.text.gather()),
+            (LineEnding::Unix, "a\r\nb\n".into())
+        );
+        buffer.write(&path).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "a\r\nb\n");
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn reload_changed_files() {
+        let path = std::env::temp_dir().join(format!("tek-autoread-{}", std::process::id()));
+        std::fs::write(&path, "one\ntwo").unwrap();
+        let mut editor = super::Editor::new(Size { width: 80, height: 25 });
+        editor.edit(path.clone()).unwrap();
+        let id = editor.window_focus();
+        editor.windows[id].cursor = Position { x: 2, y: 1 };
+        let buffer = editor.windows[id].view.unwrap().buffer;
+        let touch = |text| {
+            std::fs::write(&path, text).unwrap();
+            let later = std::time::SystemTime::now() + std::time::Duration::from_secs(10);
+            std::fs::File::options().write(true).open(&path).unwrap().set_modified(later).unwrap();
+        };
+        touch("three");
+        editor.reload_changed_files();
+        assert_eq!(editor.buffers[buffer].text.gather(), "three");
+        assert_eq!(editor.windows[id].cursor, Position { x: 2, y: 0 });
+        assert!(editor.status.as_ref().unwrap().ends_with("\" file changed on disk, reloaded"));
+        editor.reload_changed_files();
+        editor.buffers[buffer].modified = true;
+        touch("four");
+        editor.reload_changed_files();
+        assert_eq!(editor.buffers[buffer].text.gather(), "three");
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn write_buffer() {
+        let path = std::env::temp_dir().join(format!("tek-write-{}", std::process::id()));
+        std::fs::write(&path, "abc").unwrap();
+        let mut editor = super::Editor::new(Size { width: 80, height: 25 });
+        editor.edit(path.clone()).unwrap();
+        editor.delete_chars(1);
+        let error = editor.check_unsaved().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "No write since last change for buffer 1 (add ! to override)"
+        );
+        let later = std::time::SystemTime::now() + std::time::Duration::from_secs(10);
+        std::fs::File::options().write(true).open(&path).unwrap().set_modified(later).unwrap();
+        let error = editor.write_buffer(None, false).unwrap_err();
+        assert!(error.to_string().starts_with("The file has been changed since reading it"));
+        editor.write_buffer(None, true).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "bc\n");
+        assert!(editor.check_unsaved().is_ok());
+        editor.edit_new();
+        editor.insert_char('x');
+        let error = editor.write_all(false).unwrap_err();
+        assert_eq!(error.to_string(), "No file name for buffer 2");
+        editor.delete_buffer(None, true).unwrap();
+        editor.delete_chars(1);
+        editor.write_all(false).unwrap();
+        assert_eq!(editor.status.as_deref(), Some("1 buffer written"));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "c\n");
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn fixendofline() {
+        let path = std::env::temp_dir().join(format!("tek-eol-{}", std::process::id()));
+        std::fs::write(&path, "a\nb").unwrap();
+        let mut buffer = super::Buffer::read(path.clone()).unwrap();
+        assert!(buffer.no_end_of_line);
+        buffer.settings.fixendofline = false;
+        buffer.save(path.clone(), false).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "a\nb");
+        assert!(buffer.no_end_of_line);
+        buffer.settings.fixendofline = true;
+        buffer.save(path.clone(), false).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "a\nb\n");
+        assert!(!buffer.no_end_of_line);
+        buffer.text.insert(3, "\n");
+        buffer.save(path.clone(), false).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "a\nb\n");
+        buffer.text = String::new().into();
+        buffer.save(path.clone(), false).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "");
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn undo() {
+        let mut editor = editor_with_text("one\ntwo\nthree");
+        editor.delete_lines(1);
+        editor.delete_chars(2);
+        editor.join_lines(1);
+        assert_eq!(text(&editor), "o three");
+        editor.undo(2, false);
+        assert_eq!(text(&editor), "two\nthree");
+        assert_eq!(editor.status.as_deref(), Some("2 changes earlier"));
+        editor.undo(5, false);
+        assert_eq!(text(&editor), "one\ntwo\nthree");
+        assert_eq!(editor.status.as_deref(), Some("1 change earlier"));
+        editor.undo(1, false);
+        assert_eq!(editor.status.as_deref(), Some("Already at oldest change"));
+        editor.undo(2, true);
+        assert_eq!(text(&editor), "o\nthree");
+        editor.show_undo_list();
+        assert_eq!(editor.status.as_deref(), Some("Changes: 2 to undo, 1 to redo"));
+        editor.delete_chars(1);
+        editor.undo(1, true);
+        assert_eq!(editor.status.as_deref(), Some("Already at newest change"));
+    }
+
+    #[test]
+    fn undo_insert() {
+        let mut editor = editor_with_text("one\ntwo");
+        editor.delete_chars(1);
+        editor.begin_insert();
+        "ab\nc".chars().for_each(|character| editor.insert_char(character));
+        editor.delete_char_backward();
+        editor.set_mode(super::Mode::Normal);
+        assert_eq!(text(&editor), "ab\nne\ntwo");
+        editor.undo(1, false);
+        assert_eq!(text(&editor), "ne\ntwo");
+
+        let id = editor.window_focus();
+        editor.windows[id].cursor = Position { x: 0, y: 0 };
+        editor.change_lines(1);
+        editor.insert_char('x');
+        editor.click(Position { x: 1, y: 2 });
+        editor.insert_char('y');
+        editor.set_mode(super::Mode::Normal);
+        assert_eq!(text(&editor), "x\nytwo");
+        editor.undo(1, false);
+        assert_eq!(text(&editor), "x\ntwo");
+        editor.undo(1, false);
+        assert_eq!(text(&editor), "ne\ntwo");
+    }
+
+    #[test]
+    fn registers() {
+        let mut editor = editor_with_text("one\ntwo\nthree");
+        editor.register = 'a';
+        editor.yank_lines(1);
+        editor.register = 'A';
+        editor.apply_to_motion(super::Operator::Yank, '$', 1);
+        editor.register = '"';
+        editor.delete_chars(2);
+        let register = |editor: &super::Editor, name| {
+            let register = &editor.registers[&name];
+            (register.text.clone(), register.linewise)
+        };
+        assert_eq!(register(&editor, 'a'), (String::from("one\none"), true));
+        assert_eq!(register(&editor, '"'), (String::from("on"), false));
+        assert_eq!(text(&editor), "e\ntwo\nthree");
+
+        editor.paste(2, false);
+        assert_eq!(text(&editor), "eonon\ntwo\nthree");
+        assert_eq!(editor.windows[editor.window_focus()].cursor, Position { x: 4, y: 0 });
+        editor.register = 'a';
+        editor.paste(1, true);
+        assert_eq!(text(&editor), "one\none\neonon\ntwo\nthree");
+        assert_eq!(editor.windows[editor.window_focus()].cursor, Position { x: 0, y: 0 });
+        editor.register = 'b';
+        editor.paste(1, true);
+        assert_eq!(editor.status.as_deref(), Some("Nothing in register b"));
+
+        editor.register = 'b';
+        editor.delete_lines(1);
+        assert_eq!(register(&editor, 'b'), (String::from("one"), true));
+        assert_eq!(register(&editor, '"'), register(&editor, 'b'));
+    }
+
+    #[test]
+    fn paste_lines() {
+        let mut editor = editor_with_text("a\n  b");
+        let id = editor.window_focus();
+        editor.yank_lines(2);
+        editor.windows[id].cursor = Position { x: 1, y: 1 };
+        editor.paste(1, false);
+        assert_eq!(text(&editor), "a\n  b\na\n  b");
+        assert_eq!(editor.windows[id].cursor, Position { x: 0, y: 2 });
+        editor.windows[id].cursor.y = 3;
+        editor.yank_lines(1);
+        editor.paste(2, true);
+        assert_eq!(text(&editor), "a\n  b\na\n  b\n  b\n  b");
+        assert_eq!(editor.windows[id].cursor, Position { x: 2, y: 3 });
+        editor.undo(1, false);
+        assert_eq!(text(&editor), "a\n  b\na\n  b");
+    }
+
+    #[test]
+    fn sort_lines() {
+        let mut editor = editor_with_text("b10\na\nb2\n\nx-3\na\n");
+        editor.sort_lines(None, super::Sort::default());
+        assert_eq!(text(&editor), "\na\na\nb10\nb2\nx-3\n");
+        editor.sort_lines(None, super::Sort { numeric: true, unique: true, ..Default::default() });
+        assert_eq!(text(&editor), "\na\nx-3\nb2\nb10\n");
+        editor.sort_lines(Some(1..3), super::Sort { reverse: true, ..Default::default() });
+        assert_eq!(text(&editor), "\nx-3\na\nb2\nb10\n");
+        editor.undo(1, false);
+        assert_eq!(text(&editor), "\na\nx-3\nb2\nb10\n");
+        assert_eq!(super::first_number("v1.25"), Some(1));
+    }
+
+    #[test]
+    fn unchanged_text() {
+        let mut editor = editor_with_text("b\na\n\n");
+        let id = editor.window_focus();
+        editor.windows[id].cursor = Position { x: 0, y: 2 };
+        editor.delete_chars(1);
+        editor.toggle_case(1);
+        editor.join_lines(1);
+        editor.sort_lines(Some(0..1), super::Sort::default());
+        assert!(!editor.buffers[VecIndex::new(0)].modified);
+        assert_eq!(editor.buffers[VecIndex::new(0)].text.undo_counts(), (0, 0));
+        editor.sort_lines(None, super::Sort::default());
+        assert_eq!(text(&editor), "\na\nb\n");
+        assert!(editor.buffers[VecIndex::new(0)].modified);
+        editor.windows[id].cursor = Position { x: 0, y: 0 };
+        editor.delete_chars(1);
+        editor.undo(1, false);
+        assert_eq!(text(&editor), "b\na\n\n");
+        assert_eq!(editor.status.as_deref(), Some("1 change earlier"));
+    }
+
+    #[test]
+    fn delete_matching_lines() {
+        let mut editor = editor_with_text("ab\nb\nAb\nc\nab");
+        editor.delete_matching_lines(None, "x", false);
+        assert_eq!(editor.status.as_deref(), Some("Pattern not found: x"));
+        editor.delete_matching_lines(None, "ab", false);
+        assert_eq!(text(&editor), "b\nAb\nc");
+        assert_eq!(editor.status.as_deref(), Some("Deleted 2 lines"));
+        editor.delete_matching_lines(Some(1..3), "b", true);
+        assert_eq!(text(&editor), "b\nAb");
+        editor.undo(1, false);
+        assert_eq!(text(&editor), "b\nAb\nc");
+        editor.delete_matching_lines(None, "x", true);
+        assert_eq!(text(&editor), "");
+    }
+
+    #[test]
+    fn substitute() {
+        let mut editor = editor_with_text("a1 a2\nb3\na4\n");
+        editor.substitute(None, "a(\\d)", "<$1>", false);
+        assert_eq!(text(&editor), "<1> a2\nb3\na4\n");
+        assert_eq!(editor.status.as_deref(), Some("1 substitution on 1 line"));
+        editor.substitute(Some(0..usize::MAX), "\\d", "#", true);
+        assert_eq!(text(&editor), "<#> a#\nb#\na#\n");
+        assert_eq!(editor.status.as_deref(), Some("4 substitutions on 3 lines"));
+        editor.undo(1, false);
+        assert_eq!(text(&editor), "<1> a2\nb3\na4\n");
+        editor.substitute(Some(1..2), "a", "x", true);
+        assert_eq!(editor.status.as_deref(), Some("Pattern not found: a"));
+        editor.substitute(None, "(", "x", true);
+        assert_eq!(editor.status.as_deref(), Some("Invalid pattern: unclosed group"));
+        editor.settings.regex = false;
+        editor.search = Some(String::from("("));
+        editor.substitute(Some(0..usize::MAX), "", ".", true);
+        assert_eq!(text(&editor), "<1> a2\nb3\na4\n");
+        editor.substitute(Some(0..usize::MAX), "<1>", "(", true);
+        editor.substitute(Some(0..usize::MAX), "", ".", true);
+        assert_eq!(text(&editor), ". a2\nb3\na4\n");
+    }
+
+    #[test]
+    fn trim_trailing_whitespace() {
+        let mut editor = editor_with_text("a  \n \t\nb\tc\t");
+        let id = editor.window_focus();
+        editor.windows[id].cursor = Position { x: 2, y: 0 };
+        editor.trim_trailing_whitespace();
+        assert_eq!(text(&editor), "a\n\nb\tc");
+        assert_eq!(editor.status.as_deref(), Some("Trimmed trailing whitespace on 3 lines"));
+        assert_eq!(editor.windows[id].cursor, Position { x: 0, y: 0 });
+
+        let path = std::env::temp_dir().join(format!("tek-trim-{}", std::process::id()));
+        std::fs::write(&path, "x \ny\n").unwrap();
+        editor.edit(path.clone()).unwrap();
+        editor.write_buffer(None, false).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "x \ny\n");
+        let id = editor.windows[editor.window_focus()].view.unwrap().buffer;
+        editor.buffers[id].settings.trim_trailing_whitespace = true;
+        editor.write_buffer(None, false).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "x\ny\n");
+        assert!(!editor.buffers[id].modified);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn goto_line() {
+        let mut editor = editor_with_text("a\n  b\nc\n");
+        let cursor = |editor: &super::Editor| editor.windows[editor.window_focus()].cursor;
+        editor.goto_line(1);
+        assert_eq!(cursor(&editor), Position { x: 2, y: 1 });
+        editor.goto_line(usize::MAX);
+        assert_eq!(cursor(&editor), Position { x: 0, y: 2 });
+    }
+
+    #[test]
+    fn show_file_info() {
+        let mut editor = editor_with_text("a\nb\nc\n");
+        editor.show_file_info();
+        assert_eq!(editor.status.as_deref(), Some("[No Name] 3 lines --33%--"));
+        editor.delete_chars(1);
+        let id = editor.window_focus();
+        editor.windows[id].cursor.y = 3;
+        editor.show_file_info();
+        assert_eq!(editor.status.as_deref(), Some("[No Name] [Modified] 3 lines --100%--"));
+        let mut editor = editor_with_text("");
+        editor.show_file_info();
+        assert_eq!(editor.status.as_deref(), Some("[No Name] --No lines in buffer--"));
+    }
+
+    #[test]
+    fn swap_file() {
+        let path = std::env::temp_dir().join(format!("tek-swap-{}", std::process::id()));
+        let swap = path.with_file_name(format!(".tek-swap-{}.tek.swp", std::process::id()));
+        std::fs::write(&path, "abc").unwrap();
+        let mut editor = super::Editor::new(Size { width: 80, height: 25 });
+        editor.edit(path.clone()).unwrap();
+        editor.write_swap_files();
+        assert!(!swap.exists());
+        editor.delete_chars(1);
+        editor.write_swap_files();
+        assert_eq!(std::fs::read_to_string(&swap).unwrap(), "bc");
+
+        let mut other = super::Editor::new(Size { width: 80, height: 25 });
+        other.edit(path.clone()).unwrap();
+        assert!(other.status.as_ref().unwrap().starts_with("Found a swap file"));
+        other.recover().unwrap();
+        assert_eq!(text(&other), "bc");
+        other.write_buffer(None, false).unwrap();
+        assert!(!swap.exists());
+        assert_eq!(
+            other.recover().unwrap_err().to_string(),
+            format!("No swap file found for \"{}\"", path.file_name().unwrap().to_string_lossy())
+        );
+
+        editor.remove_swap_files();
+
+        std::fs::write(&path, b"a\xff").unwrap();
+        std::fs::write(&swap, "a").unwrap();
+        let mut other = super::Editor::new(Size { width: 80, height: 25 });
+        other.edit(path.clone()).unwrap();
+        let name = path.file_name().unwrap().to_string_lossy();
+        assert_eq!(
+            other.status,
+            Some(format!("\"{name}\" [converted], found a swap file, :recover to restore it"))
+        );
+        std::fs::remove_file(swap).unwrap();
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn tick() {
+        use std::time::Duration;
+        let mut editor = super::Editor::new(Size { width: 80, height: 25 });
+        let now = editor.last_file_check;
+        assert_eq!(editor.time_until_tick(now), None);
+        editor.settings.autoread = true;
+        assert_eq!(editor.time_until_tick(now), Some(super::FILE_CHECK_INTERVAL));
+        editor.settings.autoread = false;
+        editor.emit_message(String::from("message"));
+        let now = editor.status_time;
+        assert_eq!(editor.time_until_tick(now), None);
+        editor.tick(now + Duration::from_secs(60));
+        assert!(editor.status.is_some());
+        editor.status_shown = true;
+        assert_eq!(editor.time_until_tick(now), Some(super::MESSAGE_TIMEOUT));
+        editor.tick(now + Duration::from_secs(1));
+        assert!(editor.status.is_some());
+        assert_eq!(editor.time_until_tick(now + Duration::from_secs(60)), Some(Duration::ZERO));
+        editor.tick(now + Duration::from_secs(60));
+        assert!(editor.status.is_none());
+    }
+
+    #[test]
+    fn edit_new() {
+        let mut editor = super::Editor::new(Size { width: 80, height: 25 });
+        editor.edit_new();
+        let view = editor.windows[editor.window_focus()].view.unwrap();
+        assert!(editor.buffers[view.buffer].file_info.is_none());
+        let error = editor.write_buffer(None, false).unwrap_err();
+        assert_eq!(error.to_string(), "No file name");
+    }
+
+    #[test]
+    fn delete_buffer() {
+        let mut editor = editor_with_text("a");
+        editor.delete_chars(1);
+        editor.edit_new();
+        editor.edit_new();
+        let id = editor.window_focus();
+        let view = |editor: &super::Editor| editor.windows[id].view.unwrap().buffer.get();
+        assert_eq!(view(&editor), 2);
+        let error = editor.delete_buffer(Some(VecIndex::new(0)), false).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "No write since last change for buffer 1 (add ! to override)"
+        );
+        editor.delete_buffer(Some(VecIndex::new(0)), true).unwrap();
+        assert!(editor.buffers.get(VecIndex::new(0)).is_none());
+        assert_eq!((editor.buffers.len(), view(&editor)), (2, 2));
+        editor.delete_buffer(None, false).unwrap();
+        assert_eq!(view(&editor), 1);
+        editor.delete_buffer(None, false).unwrap();
+        assert_eq!(editor.buffers.len(), 1);
+        assert!(editor.buffers[editor.windows[id].view.unwrap().buffer].file_info.is_none());
+        let error = editor.delete_buffer(Some(VecIndex::new(5)), false).unwrap_err();
+        assert_eq!(error.to_string(), "No buffer 6");
+    }
+
+    #[test]
+    fn move_window_to_edge() {
+        let mut editor = super::Editor::new(Size { width: 80, height: 25 });
+        editor.vertical_split_window(None);
+        editor.horizontal_split_window(None);
+        editor.move_window_to_edge(crate::util::Direction::Down);
+        assert_eq!(area(&editor, 0), (Position { x: 40, y: 0 }, Size { width: 40, height: 12 }));
+        assert_eq!(area(&editor, 1), (Position { x: 0, y: 0 }, Size { width: 40, height: 12 }));
+        assert_eq!(area(&editor, 2), (Position { x: 0, y: 12 }, Size { width: 80, height: 12 }));
+        editor.move_window_to_edge(crate::util::Direction::Left);
+        assert_eq!(area(&editor, 0), (Position { x: 60, y: 0 }, Size { width: 20, height: 24 }));
+        assert_eq!(area(&editor, 1), (Position { x: 40, y: 0 }, Size { width: 20, height: 24 }));
+        assert_eq!(area(&editor, 2), (Position { x: 0, y: 0 }, Size { width: 40, height: 24 }));
+    }
+
+    #[test]
+    fn resize_window() {
+        let mut editor = super::Editor::new(Size { width: 80, height: 25 });
+        editor.vertical_split_window(None);
+        editor.horizontal_split_window(None);
+        editor.resize_window(true, 2);
+        assert_eq!(area(&editor, 0), (Position { x: 0, y: 0 }, Size { width: 42, height: 12 }));
+        assert_eq!(area(&editor, 1), (Position { x: 42, y: 0 }, Size { width: 38, height: 24 }));
+        assert_eq!(area(&editor, 2), (Position { x: 0, y: 12 }, Size { width: 42, height: 12 }));
+        editor.resize_window(false, -7);
+        assert_eq!(area(&editor, 0), (Position { x: 0, y: 0 }, Size { width: 42, height: 12 }));
+        editor.resize_window(false, -6);
+        assert_eq!(area(&editor, 0), (Position { x: 0, y: 0 }, Size { width: 42, height: 6 }));
+        assert_eq!(area(&editor, 2), (Position { x: 0, y: 6 }, Size { width: 42, height: 18 }));
+        editor.move_focus(crate::util::Direction::Right);
+        editor.resize_window(true, 1);
+        assert_eq!(area(&editor, 1), (Position { x: 41, y: 0 }, Size { width: 39, height: 24 }));
+        assert_eq!(area(&editor, 2), (Position { x: 0, y: 6 }, Size { width: 41, height: 18 }));
+    }
+
+    #[test]
+    fn search() {
+        let mut editor = editor_with_text("one Two\nthree two\ntwo");
+        let cursor = |editor: &super::Editor| editor.windows[editor.window_focus()].cursor;
+        editor.search(String::from("two"), false);
+        assert_eq!(cursor(&editor), Position { x: 6, y: 1 });
+        editor.search_next(false);
+        assert_eq!(cursor(&editor), Position { x: 0, y: 2 });
+        editor.search_next(false);
+        assert_eq!(cursor(&editor), Position { x: 6, y: 1 });
+        assert_eq!(editor.status.as_deref(), Some("search hit BOTTOM, continuing at TOP"));
+        editor.settings.ignorecase = true;
+        editor.search_next(true);
+        assert_eq!(cursor(&editor), Position { x: 4, y: 0 });
+        editor.settings.smartcase = true;
+        editor.search(String::from("Two"), true);
+        assert_eq!(cursor(&editor), Position { x: 4, y: 0 });
+        editor.search(String::from("four"), false);
+        assert_eq!(editor.status.as_deref(), Some("Pattern not found: four"));
+    }
+
+    #[test]
+    fn search_word() {
+        let mut editor = editor_with_text("let ab = a;\nab_c(ab)\n  ab");
+        let id = editor.window_focus();
+        let cursor = |editor: &super::Editor| editor.windows[id].cursor;
+        editor.windows[id].cursor.x = 3;
+        editor.search_word(false);
+        assert_eq!(editor.search.as_deref(), Some("\\<ab\\>"));
+        assert_eq!(cursor(&editor), Position { x: 5, y: 1 });
+        editor.search_next(false);
+        assert_eq!(cursor(&editor), Position { x: 2, y: 2 });
+        editor.search_word(true);
+        assert_eq!(cursor(&editor), Position { x: 5, y: 1 });
+        editor.windows[id].cursor.x = 2;
+        editor.search_word(true);
+        assert_eq!(cursor(&editor), Position { x: 0, y: 1 });
+        assert_eq!(editor.search.as_deref(), Some("\\<ab_c\\>"));
+    }
+
+    #[test]
+    fn match_bracket() {
+        let mut editor = editor_with_text("f(a[0], (b)) {\n  x\n}");
+        let id = editor.window_focus();
+        editor.match_bracket();
+        assert_eq!(editor.windows[id].cursor, Position { x: 11, y: 0 });
+        editor.match_bracket();
+        assert_eq!(editor.windows[id].cursor, Position { x: 1, y: 0 });
+        editor.windows[id].cursor.x = 12;
+        editor.match_bracket();
+        assert_eq!(editor.windows[id].cursor, Position { x: 0, y: 2 });
+        editor.match_bracket();
+        assert_eq!(editor.windows[id].cursor, Position { x: 13, y: 0 });
+        editor.windows[id].cursor = Position { x: 0, y: 1 };
+        editor.match_bracket();
+        assert_eq!(editor.status.as_deref(), Some("No bracket on the line"));
+        let mut editor = editor_with_text("(()");
+        editor.match_bracket();
+        assert_eq!(editor.status.as_deref(), Some("No matching bracket"));
+    }
+
+    #[test]
+    fn marks() {
+        let mut editor = editor_with_text("one\ntwo\nthree");
+        let id = editor.window_focus();
+        editor.windows[id].cursor = Position { x: 4, y: 2 };
+        editor.set_mark('a');
+        editor.edit_new();
+        editor.jump_to_mark('a');
+        assert_eq!(editor.windows[id].view.unwrap().buffer.get(), 0);
+        assert_eq!(editor.windows[id].cursor, Position { x: 4, y: 2 });
+        editor.delete_lines(1);
+        editor.windows[id].cursor = Position::default();
+        editor.jump_to_mark('a');
+        assert_eq!(editor.windows[id].cursor, Position { x: 2, y: 1 });
+        editor.jump_to_mark('b');
+        assert_eq!(editor.status.as_deref(), Some("Mark not set"));
+    }
+
+    #[test]
+    fn jump_list() {
+        let mut editor = editor_with_text("one\n  two\nthree\nfour");
+        let id = editor.window_focus();
+        let line = |editor: &super::Editor| editor.windows[id].cursor.y;
+        editor.goto_line(usize::MAX);
+        assert_eq!(line(&editor), 3);
+        editor.goto_line(1);
+        assert_eq!(editor.windows[id].cursor, Position { x: 2, y: 1 });
+        editor.search(String::from("three"), false);
+        assert_eq!(line(&editor), 2);
+        editor.jump_back();
+        assert_eq!(line(&editor), 1);
+        editor.jump_back();
+        assert_eq!(line(&editor), 3);
+        editor.jump_back();
+        assert_eq!(line(&editor), 0);
+        editor.jump_back();
+        assert_eq!(line(&editor), 0);
+        editor.jump_forward();
+        editor.jump_forward();
+        editor.jump_forward();
+        assert_eq!(line(&editor), 2);
+        editor.jump_forward();
+        assert_eq!(line(&editor), 2);
+
+        // Without a view there is nowhere to jump from.
+        let mut editor = super::Editor::new(Size { width: 80, height: 25 });
+        editor.jump_back();
+        editor.jump_forward();
+        assert!(editor.jumps.is_empty());
+    }
+
+    #[test]
+    fn find_char() {
+        use super::FindChar;
+        let mut editor = editor_with_text("a.b.c.d");
+        let id = editor.window_focus();
+        let column = |editor: &super::Editor| editor.windows[id].cursor.x;
+        editor.find_char(FindChar { character: '.', backward: false, till: false }, 2);
+        assert_eq!(column(&editor), 3);
+        editor.repeat_find_char(false, 1);
+        assert_eq!(column(&editor), 5);
+        editor.repeat_find_char(true, 1);
+        assert_eq!(column(&editor), 3);
+        editor.find_char(FindChar { character: '.', backward: false, till: true }, 1);
+        assert_eq!(column(&editor), 4);
+        editor.repeat_find_char(false, 1);
+        assert_eq!(column(&editor), 4);
+        editor.find_char(FindChar { character: 'a', backward: true, till: true }, 1);
+        assert_eq!(column(&editor), 1);
+        editor.find_char(FindChar { character: 'x', backward: false, till: false }, 1);
+        assert_eq!(column(&editor), 1);
+    }
+
+    #[test]
+    fn preview_search() {
+        let mut editor = editor_with_text("one\ntwo\nthree");
+        let cursor = |editor: &super::Editor| editor.windows[editor.window_focus()].cursor;
+        editor.begin_search_preview();
+        assert!(editor.search_preview.is_none());
+        editor.settings.incsearch = true;
+        editor.begin_search_preview();
+        editor.preview_search("t", false);
+        assert_eq!(cursor(&editor), Position { x: 0, y: 1 });
+        editor.preview_search("th", false);
+        assert_eq!(cursor(&editor), Position { x: 0, y: 2 });
+        editor.preview_search("x", false);
+        assert_eq!(cursor(&editor), Position { x: 0, y: 0 });
+        editor.preview_search("th", false);
+        editor.end_search_preview();
+        assert_eq!(cursor(&editor), Position { x: 0, y: 0 });
+        assert!(editor.search.is_none());
+    }
+
+    #[test]
+    fn apply_to_text_object() {
+        use super::{Mode, Operator};
+        let mut editor = editor_with_text("f(\"ab\", cd ef)");
+        let id = editor.window_focus();
+        editor.windows[id].cursor.x = 4;
+        editor.apply_to_text_object(Operator::Yank, '"', true);
+        assert_eq!(editor.registers[&'"'].text, "\"ab\"");
+        assert_eq!(editor.windows[id].cursor.x, 2);
+        editor.apply_to_text_object(Operator::Delete, '"', false);
+        assert_eq!(text(&editor), "f(\"\", cd ef)");
+        editor.windows[id].cursor.x = 6;
+        editor.apply_to_text_object(Operator::Delete, 'w', true);
+        assert_eq!(text(&editor), "f(\"\", ef)");
+        assert_eq!(editor.registers[&'"'].text, "cd ");
+        editor.repeat_change(None);
+        assert_eq!(text(&editor), "f(\"\",)");
+        assert_eq!(editor.registers[&'"'].text, " ef");
+        editor.apply_to_text_object(Operator::Change, 'b', false);
+        assert_eq!(text(&editor), "f()");
+        assert_eq!((editor.mode, editor.windows[id].cursor.x), (Mode::Insert, 2));
+    }
+
+    #[test]
+    fn begin_insert_at() {
+        let mut editor = editor_with_text("  abc");
+        let id = editor.window_focus();
+        let column = |editor: &mut super::Editor, key| {
+            editor.set_mode(super::Mode::Normal);
+            editor.begin_insert_at(key);
+            assert_eq!(editor.mode, super::Mode::Insert);
+            editor.windows[id].cursor.x
+        };
+        assert_eq!(column(&mut editor, 'I'), 2);
+        assert_eq!(column(&mut editor, 'a'), 3);
+        assert_eq!(column(&mut editor, 'A'), 5);
+        assert_eq!(column(&mut editor, 'a'), 5);
+    }
+
+    #[test]
+    fn change() {
+        use super::{Mode, Operator};
+        let mut editor = editor_with_text("one two three\nfour\nfive");
+        let id = editor.window_focus();
+        editor.apply_to_motion(Operator::Change, 'w', 1);
+        assert_eq!(text(&editor), " two three\nfour\nfive");
+        assert_eq!((editor.mode, editor.windows[id].cursor.x), (Mode::Insert, 0));
+        editor.set_mode(Mode::Normal);
+        editor.apply_to_motion(Operator::Delete, 'w', 1);
+        assert_eq!(text(&editor), "two three\nfour\nfive");
+        editor.repeat_change(None);
+        assert_eq!(text(&editor), "three\nfour\nfive");
+        editor.windows[id].cursor.x = 2;
+        editor.apply_to_motion(Operator::Change, '$', 1);
+        assert_eq!(text(&editor), "th\nfour\nfive");
+        assert_eq!((editor.registers[&'"'].text.as_str(), editor.windows[id].cursor.x), ("ree", 2));
+        editor.set_mode(Mode::Normal);
+        editor.windows[id].cursor.y = 1;
+        editor.change_lines(5);
+        assert_eq!(text(&editor), "th\n");
+        assert_eq!(editor.registers[&'"'].text, "four\nfive");
+        assert_eq!(editor.windows[id].cursor, Position { x: 0, y: 1 });
+        editor.set_mode(Mode::Normal);
+        editor.windows[id].cursor = Position { x: 0, y: 0 };
+        editor.buffers[VecIndex::new(0)].settings.modifiable = false;
+        editor.apply_to_motion(Operator::Delete, '$', 1);
+        assert_eq!(text(&editor), "th\n");
+        assert_eq!(editor.registers[&'"'].text, "four\nfive");
+    }
+
+    #[test]
+    fn toggle_case() {
+        let mut editor = editor_with_text("aBc-ßx\nyz");
+        let id = editor.window_focus();
+        editor.toggle_case(1);
+        assert_eq!(text(&editor), "ABc-ßx\nyz");
+        assert_eq!(editor.windows[id].cursor.x, 1);
+        editor.toggle_case(4);
+        assert_eq!(text(&editor), "AbC-SSx\nyz");
+        assert_eq!(editor.windows[id].cursor.x, 6);
+        editor.toggle_case(9);
+        assert_eq!(text(&editor), "AbC-SSX\nyz");
+        assert_eq!(editor.windows[id].cursor.x, 6);
+        editor.windows[id].cursor = Position { x: 0, y: 1 };
+        editor.repeat_change(None);
+        assert_eq!(text(&editor), "AbC-SSX\nYZ");
+        editor.undo(1, false);
+        assert_eq!(text(&editor), "AbC-SSX\nyz");
+    }
+
+    #[test]
+    fn replace_chars() {
+        let mut editor = editor_with_text("abcd\nef");
+        let id = editor.window_focus();
+        editor.replace_chars('x', 2);
+        assert_eq!(text(&editor), "xxcd\nef");
+        assert_eq!(editor.windows[id].cursor, Position { x: 1, y: 0 });
+        editor.replace_chars('y', 4);
+        assert_eq!(text(&editor), "xxcd\nef");
+        editor.windows[id].cursor.x = 2;
+        editor.repeat_change(None);
+        assert_eq!(text(&editor), "xxxx\nef");
+        assert_eq!(editor.windows[id].cursor, Position { x: 3, y: 0 });
+        editor.windows[id].cursor = Position { x: 1, y: 1 };
+        editor.replace_chars('\n', 1);
+        assert_eq!(text(&editor), "xxxx\ne\n");
+        assert_eq!(editor.windows[id].cursor, Position { x: 0, y: 2 });
+    }
+
+    #[test]
+    fn split_window() {
+        let mut editor = editor_with_text("abc\ndef");
+        editor.settings.splitfocus = true;
+        let first = editor.window_focus();
+        editor.windows[first].cursor = Position { x: 2, y: 1 };
+        let second = editor.vertical_split_window(None).unwrap();
+        assert_eq!(editor.window_focus(), second);
+        let (view, other) = (editor.windows[first].view.unwrap(), editor.windows[second].view);
+        assert_eq!(editor.windows[first].size, Size { width: 40, height: 24 });
+        assert_eq!(other.unwrap().buffer, view.buffer);
+        assert_eq!(editor.windows[second].cursor, Position { x: 2, y: 1 });
+        editor.move_cursor(crate::util::Direction::Up);
+        assert_eq!(editor.windows[first].cursor, Position { x: 2, y: 1 });
+        editor.settings.splitfocus = false;
+        editor.horizontal_split_window(None).unwrap();
+        assert_eq!(editor.window_focus(), second);
+    }
+
+    #[test]
+    fn split_window_length() {
+        let mut editor = editor_with_text("abc");
+        let first = editor.window_focus();
+        let second = editor.horizontal_split_window(Some(10)).unwrap();
+        assert_eq!(area(&editor, 0), (Position { x: 0, y: 0 }, Size { width: 80, height: 14 }));
+        assert_eq!(area(&editor, 1), (Position { x: 0, y: 14 }, Size { width: 80, height: 10 }));
+        assert_eq!(editor.vertical_split_window(Some(78)), None);
+        assert_eq!(editor.status.as_deref(), Some("Invalid window size: 78"));
+        assert_eq!(editor.horizontal_split_window(Some(2)), None);
+        let third = editor.vertical_split_window(Some(77)).unwrap();
+        assert_eq!(editor.windows[first].size, Size { width: 3, height: 14 });
+        assert_eq!(editor.windows[third].size, Size { width: 77, height: 14 });
+        assert_eq!(editor.windows[second].size.height, 10);
+    }
+
+    #[test]
+    fn split_and_edit() {
+        let path = std::env::temp_dir().join(format!("tek-split-{}", std::process::id()));
+        std::fs::write(&path, "split").unwrap();
+        let mut editor = editor_with_text("a");
+        let focused_text = |editor: &super::Editor| {
+            let view = editor.windows[editor.window_focus()].view.unwrap();
+            editor.buffers[view.buffer].text.gather()
+        };
+        let first = editor.window_focus();
+        editor.split_and_edit(true, None, path.clone()).unwrap();
+        assert_eq!(editor.tabs[0].open_windows.len(), 2);
+        assert_ne!(editor.window_focus(), first);
+        assert_eq!(focused_text(&editor), "split");
+        editor.set_window_focus(first);
+        assert_eq!(focused_text(&editor), "a");
+        let error = editor.split_and_edit(false, None, path.with_extension("missing")).unwrap_err();
+        assert!(error.to_string().starts_with("E212"));
+        assert_eq!(editor.tabs[0].open_windows.len(), 2);
+        assert_eq!(editor.window_focus(), first);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn help() {
+        let mut editor = editor_with_text("a");
+        let buffer_count = |editor: &super::Editor| editor.buffers.indices().count();
+        editor.show_scratch("Help", "old");
+        editor.show_scratch("Help", "help");
+        assert_eq!(buffer_count(&editor), 2);
+        let view = editor.windows[editor.window_focus()].view.unwrap();
+        assert_eq!(editor.buffers[view.buffer].scratch, Some("Help"));
+        assert!(editor.buffers[view.buffer].file_info.is_none());
+        editor.insert_char('x');
+        assert_eq!(editor.buffers[view.buffer].text.gather(), "help");
+        editor.close_scratch();
+        assert_eq!(buffer_count(&editor), 1);
+        assert_eq!(text(&editor), "a");
+        editor.close_scratch();
+        assert_eq!(buffer_count(&editor), 1);
+    }
+
+    #[test]
+    fn explore() {
+        let directory = std::env::temp_dir().join(format!("tek-explore-{}", std::process::id()));
+        std::fs::create_dir_all(directory.join("sub")).unwrap();
+        std::fs::write(directory.join("b.txt"), "b").unwrap();
+        std::fs::write(directory.join("sub").join("a.txt"), "a").unwrap();
+        let mut editor = editor_with_text("");
+        let focused_text = |editor: &super::Editor| {
+            let view = editor.windows[editor.window_focus()].view.unwrap();
+            editor.buffers[view.buffer].text.gather()
+        };
+        editor.edit(directory.clone()).unwrap();
+        assert_eq!(focused_text(&editor), "../\nb.txt\nsub/");
+        let id = editor.window_focus();
+        editor.windows[id].cursor.y = 2;
+        editor.open_directory_entry().unwrap();
+        assert_eq!(focused_text(&editor), "../\na.txt");
+        assert_eq!(editor.windows[id].cursor, Position::default());
+        editor.open_directory_entry().unwrap();
+        assert_eq!(focused_text(&editor), "../\nb.txt\nsub/");
+        editor.windows[id].cursor.y = 1;
+        editor.open_directory_entry().unwrap();
+        assert_eq!(focused_text(&editor), "b");
+        editor.open_directory_entry().unwrap();
+        assert_eq!(focused_text(&editor), "b");
+        assert!(editor.explore(directory.join("missing")).is_err());
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn rotate_focus() {
+        let mut editor = super::Editor::new(Size { width: 80, height: 25 });
+        let top_left = editor.window_focus();
+        let right = editor.vertical_split_window(None).unwrap();
+        editor.set_window_focus(top_left);
+        let bottom_left = editor.horizontal_split_window(None).unwrap();
+        editor.tab_open();
+        editor.tab_previous();
+        editor.set_window_focus(top_left);
+        editor.rotate_focus_forward();
+        assert_eq!(editor.window_focus(), right);
+        editor.rotate_focus_forward();
+        assert_eq!(editor.window_focus(), bottom_left);
+        editor.rotate_focus_forward();
+        assert_eq!(editor.window_focus(), top_left);
+        editor.rotate_focus_backward();
+        assert_eq!(editor.window_focus(), bottom_left);
+        editor.close_window();
+        let focus = editor.window_focus();
+        editor.rotate_focus_forward();
+        assert!(![focus, bottom_left].contains(&editor.window_focus()));
+        editor.rotate_focus_forward();
+        assert_eq!(editor.window_focus(), focus);
+    }
+
+    #[test]
+    fn visual_block() {
+        use crate::util::Direction;
+        let mut editor = editor_with_text("abcd\nef\n\tghi\nabcd");
+        let id = editor.window_focus();
+        editor.windows[id].cursor.x = 1;
+        editor.begin_visual_block();
+        editor.move_cursor(Direction::Right);
+        editor.move_cursor(Direction::Down);
+        editor.move_cursor(Direction::Down);
+        editor.move_cursor(Direction::Down);
+        assert_eq!(editor.mode, super::Mode::VisualBlock);
+        let (_, lines, columns) = editor.visual_block().unwrap();
+        assert_eq!((lines, columns), (0..4, 1..3));
+        editor.delete_block();
+        assert_eq!(text(&editor), "ad\ne\n\tghi\nad");
+        assert_eq!(editor.registers[&'"'].text, "bc\nf\n\nbc");
+        assert_eq!(
+            (editor.mode, editor.windows[id].cursor),
+            (super::Mode::Normal, Position { x: 1, y: 0 })
+        );
+    }
+
+    #[test]
+    fn block_insert() {
+        use crate::util::Direction;
+        let mut editor = editor_with_text("abcd\nx\nabcd");
+        let id = editor.window_focus();
+        editor.windows[id].cursor.x = 2;
+        editor.begin_visual_block();
+        editor.move_cursor(Direction::Down);
+        editor.move_cursor(Direction::Down);
+        editor.begin_block_insert(false);
+        assert_eq!(editor.windows[id].cursor, Position { x: 2, y: 0 });
+        editor.insert_char('-');
+        editor.insert_char('-');
+        editor.set_mode(super::Mode::Normal);
+        assert_eq!(text(&editor), "ab--cd\nx\nab--cd");
+
+        editor.windows[id].cursor = Position { x: 3, y: 0 };
+        editor.begin_visual_block();
+        editor.move_cursor(Direction::Down);
+        editor.move_cursor(Direction::Down);
+        editor.begin_block_insert(true);
+        editor.insert_char('|');
+        editor.set_mode(super::Mode::Normal);
+        assert_eq!(text(&editor), "ab--|cd\nx   |\nab--|cd");
+        assert!(matches!(&editor.last_change, Some(super::Change::Insert(text)) if text == "|"));
+    }
+
+    #[test]
+    fn shift_lines() {
+        let mut editor = editor_with_text("a\n\n  b\n\tc");
+        let id = editor.window_focus();
+        editor.shift_lines(3, true);
+        assert_eq!(text(&editor), "\ta\n\n\t  b\n\tc");
+        assert_eq!(editor.windows[id].cursor.x, 1);
+        editor.windows[id].cursor.y = 2;
+        editor.windows[id].cursor.x = 3;
+        editor.shift_lines(2, false);
+        assert_eq!(text(&editor), "\ta\n\n  b\nc");
+        assert_eq!(editor.windows[id].cursor.x, 2);
+        editor.repeat_change(None);
+        assert_eq!(text(&editor), "\ta\n\nb\nc");
+        assert_eq!(editor.windows[id].cursor.x, 0);
+
+        editor.buffers[VecIndex::new(0)].settings.expandtab = true;
+        editor.buffers[VecIndex::new(0)].settings.tabstop = 4;
+        editor.windows[id].cursor = Position::default();
+        editor.begin_visual_block();
+        editor.windows[id].cursor.y = 2;
+        editor.shift_block(true);
+        assert_eq!(text(&editor), "    \ta\n\n    b\nc");
+        assert_eq!(editor.mode, super::Mode::Normal);
+    }
+
+    #[test]
+    fn close_window() {
+        let mut editor = super::Editor::new(Size { width: 80, height: 25 });
+        editor.close_window();
+        assert_eq!(editor.tabs[0].open_windows.len(), 1);
+        editor.vertical_split_window(None);
+        editor.horizontal_split_window(None);
+        editor.close_window();
+        assert_eq!(editor.tabs[0].open_windows.len(), 2);
+        assert_eq!(area(&editor, 0), (Position { x: 40, y: 0 }, Size { width: 40, height: 24 }));
+        assert_eq!(area(&editor, 1), (Position { x: 0, y: 0 }, Size { width: 40, height: 24 }));
+        assert_eq!(editor.window_focus(), editor.tabs[0].open_windows[1]);
+        editor.close_window();
+        assert_eq!(area(&editor, 0), (Position { x: 0, y: 0 }, Size { width: 80, height: 24 }));
+        editor.vertical_split_window(None);
+        assert_eq!(editor.windows.len(), 3);
+    }
+
+    #[test]
+    fn balance_windows() {
+        let mut editor = super::Editor::new(Size { width: 80, height: 25 });
+        editor.vertical_split_window(None);
+        editor.vertical_split_window(None);
+        editor.horizontal_split_window(None);
+        editor.balance_windows();
+        assert_eq!(area(&editor, 0), (Position { x: 0, y: 0 }, Size { width: 26, height: 12 }));
+        assert_eq!(area(&editor, 1), (Position { x: 53, y: 0 }, Size { width: 27, height: 24 }));
+        assert_eq!(area(&editor, 2), (Position { x: 26, y: 0 }, Size { width: 27, height: 24 }));
+        assert_eq!(area(&editor, 3), (Position { x: 0, y: 12 }, Size { width: 26, height: 12 }));
+    }
+
+    #[test]
+    fn only_window() {
+        let mut editor = super::Editor::new(Size { width: 80, height: 25 });
+        editor.vertical_split_window(None);
+        editor.horizontal_split_window(None);
+        let focus = editor.window_focus();
+        editor.swap_window();
+        editor.only_window();
+        assert_eq!(editor.tabs[0].open_windows, [focus]);
+        assert_eq!(area(&editor, 0), (Position { x: 0, y: 0 }, Size { width: 80, height: 24 }));
+        assert_eq!(editor.window_ids().filter(|&id| editor.windows[id].is_open).count(), 1);
+    }
+
+    #[test]
+    fn tab_move() {
+        let mut editor = super::Editor::new(Size { width: 80, height: 25 });
+        editor.tab_open();
+        editor.tab_open();
+        let window = editor.window_focus();
+        editor.tab_move(0);
+        assert_eq!((editor.current_tab, editor.window_focus()), (0, window));
+        editor.tab_move(usize::MAX);
+        assert_eq!((editor.current_tab, editor.window_focus()), (2, window));
+    }
+
+    #[test]
+    fn swap_window() {
+        let mut editor = super::Editor::new(Size { width: 80, height: 25 });
+        editor.swap_window();
+        editor.vertical_split_window(None);
+        editor.swap_window();
+        assert_eq!(area(&editor, 0), (Position { x: 40, y: 0 }, Size { width: 40, height: 24 }));
+        assert_eq!(area(&editor, 1), (Position { x: 0, y: 0 }, Size { width: 40, height: 24 }));
+        editor.swap_window();
+        assert_eq!(area(&editor, 0), (Position { x: 0, y: 0 }, Size { width: 40, height: 24 }));
     }
 }
@@ -0,0 +1,111 @@
+use std::ops::Range;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Token {
+    Keyword,
+    String,
+    Comment,
+    Number,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Span {
+    pub range: Range<usize>,
+    pub token: Token,
+}
+
+// Lines are highlighted one at a time, so constructs that span lines are not recognized.
+pub trait Highlighter {
+    fn highlight_line(&self, line: &str) -> Vec<Span>;
+}
+
+pub struct Rust;
+
+#[rustfmt::skip]
+const RUST_KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+    "extern", "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move",
+    "mut", "pub", "ref", "return", "self", "Self", "static", "struct", "super", "trait", "true",
+    "type", "unsafe", "use", "where", "while",
+];
+
+pub fn for_file_type(file_type: &str) -> Option<&'static dyn Highlighter> {
+    match file_type {
+        "rust" => Some(&Rust),
+        _ => None,
+    }
+}
+
+// The end of the quoted literal that starts at `start`, or the end of the line if it is not closed.
+fn literal_end(line: &str, start: usize, quote: char) -> usize {
+    let mut escaped = false;
+    for (index, character) in line[start + 1..].char_indices() {
+        match character {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            _ if character == quote => return start + 1 + index + 1,
+            _ => {}
+        }
+    }
+    line.len()
+}
+
+fn word_end(line: &str, start: usize) -> usize {
+    line[start..]
+        .find(|character: char| !character.is_alphanumeric() && character != '_')
+        .map_or(line.len(), |index| start + index)
+}
+
+impl Highlighter for Rust {
+    fn highlight_line(&self, line: &str) -> Vec<Span> {
+        let mut spans = Vec::new();
+        let mut index = 0;
+        while let Some(character) = line[index..].chars().next() {
+            let (end, token) = match character {
+                '/' if line[index..].starts_with("//") => (line.len(), Some(Token::Comment)),
+                '"' => (literal_end(line, index, '"'), Some(Token::String)),
+                // A quote is a character literal only if it is closed, otherwise it is a lifetime.
+                '\'' if line[index + 1..].starts_with('\\')
+                    || line[index + 1..].chars().nth(1) == Some('\'') =>
+                {
+                    (literal_end(line, index, '\''), Some(Token::String))
+                }
+                '0'..='9' => (word_end(line, index), Some(Token::Number)),
+                _ if character.is_alphabetic() || character == '_' => {
+                    let end = word_end(line, index);
+                    (end, RUST_KEYWORDS.contains(&&line[index..end]).then_some(Token::Keyword))
+                }
+                _ => (index + character.len_utf8(), None),
+            };
+            if let Some(token) = token {
+                spans.push(Span { range: index..end, token });
+            }
+            index = end;
+        }
+        spans
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Highlighter, Span, Token};
+
+    fn spans(line: &str) -> Vec<(&str, Token)> {
+        let spans = super::Rust.highlight_line(line);
+        spans.into_iter().map(|Span { range, token }| (&line[range], token)).collect()
+    }
+
+    #[test]
+    fn rust() {
+        assert_eq!(
+            spans("pub fn f<'a>(x: &'a str) -> u8 { 0x1f }"),
+            [("pub", Token::Keyword), ("fn", Token::Keyword), ("0x1f", Token::Number)]
+        );
+        assert_eq!(
+            spans(r#"let s = "a \" // b"; // c"#),
+            [("let", Token::Keyword), (r#""a \" // b""#, Token::String), ("// c", Token::Comment)]
+        );
+        assert_eq!(spans(r"['\'', 'ö', x1]"), [(r"'\''", Token::String), ("'ö'", Token::String)]);
+        assert_eq!(spans("\"unclosed"), [("\"unclosed", Token::String)]);
+    }
+}
@@ -0,0 +1,81 @@
+pub struct History {
+    entries: Vec<String>,
+    capacity: usize,
+    index: Option<usize>,
+    draft: String,
+}
+
+impl History {
+    pub fn new(capacity: usize) -> History {
+        History {
+            entries: Vec::new(),
+            capacity,
+            index: None,
+            draft: String::new(),
+        }
+    }
+
+    pub fn push(&mut self, entry: String) {
+        self.reset();
+        if entry.is_empty() || self.entries.last() == Some(&entry) {
+            return;
+        }
+        self.entries.push(entry);
+        if self.entries.len() > self.capacity {
+            self.entries.remove(0);
+        }
+    }
+
+    // Stops browsing, so that the next call to `previous` starts from the most recent entry.
+    pub fn reset(&mut self) {
+        self.index = None;
+    }
+
+    // `current` is remembered when browsing begins, so that `next` can return to it.
+    pub fn previous(&mut self, current: &str) -> Option<&str> {
+        let index = match self.index {
+            Some(index) => index.checked_sub(1)?,
+            None => {
+                self.draft = current.to_owned();
+                self.entries.len().checked_sub(1)?
+            }
+        };
+        self.index = Some(index);
+        Some(&self.entries[index])
+    }
+
+    pub fn next(&mut self) -> Option<&str> {
+        let index = self.index? + 1;
+        if index == self.entries.len() {
+            self.index = None;
+            return Some(&self.draft);
+        }
+        self.index = Some(index);
+        Some(&self.entries[index])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn history() {
+        let mut history = super::History::new(3);
+        assert_eq!(history.previous("x"), None);
+        assert_eq!(history.next(), None);
+        for entry in ["a", "b", "b", "", "c", "d"] {
+            history.push(entry.to_owned());
+        }
+        assert_eq!(history.entries, ["b", "c", "d"]);
+        assert_eq!(history.previous("draft"), Some("d"));
+        assert_eq!(history.previous("ignored"), Some("c"));
+        assert_eq!(history.previous("ignored"), Some("b"));
+        assert_eq!(history.previous("ignored"), None);
+        assert_eq!(history.next(), Some("c"));
+        assert_eq!(history.next(), Some("d"));
+        assert_eq!(history.next(), Some("draft"));
+        assert_eq!(history.next(), None);
+        assert_eq!(history.previous(""), Some("d"));
+        history.reset();
+        assert_eq!(history.previous(""), Some("d"));
+    }
+}
@@ -1,11 +1,29 @@
-pub trait VecIndex {
+pub trait VecIndex: Copy {
     fn get(self) -> usize;
     fn new(index: usize) -> Self;
+    // The generation of the slot when the index was created, or `None` for indices that do not
+    // track generations and so can not be detected as stale.
+    fn generation(self) -> Option<u32> {
+        None
+    }
+    fn with_generation(index: usize, _generation: u32) -> Self {
+        Self::new(index)
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Slot<T> {
+    element: Option<T>,
+    // Incremented whenever the element is removed, which makes indices to it stale.
+    generation: u32,
 }
 
+// Removed elements leave empty slots, so the indices of the remaining elements stay valid.
+// Empty slots are reused by later pushes.
 #[derive(Clone, Debug)]
 pub struct IndexVec<T, Index: VecIndex> {
-    pub underlying: Vec<T>,
+    slots: Vec<Slot<T>>,
+    free: Vec<usize>,
     marker: std::marker::PhantomData<Index>,
 }
 
@@ -19,32 +37,70 @@ impl<T, Index: VecIndex> Default for IndexVec<T, Index> {
 impl<T, Index: VecIndex> std::ops::Index<Index> for IndexVec<T, Index> {
     type Output = T;
     fn index(&self, index: Index) -> &T {
-        &self.underlying[index.get()]
+        self.get(index).expect("index refers to a removed element")
     }
 }
 
 impl<T, Index: VecIndex> std::ops::IndexMut<Index> for IndexVec<T, Index> {
     fn index_mut(&mut self, index: Index) -> &mut T {
-        &mut self.underlying[index.get()]
+        self.get_mut(index).expect("index refers to a removed element")
     }
 }
 
 impl<T, Index: VecIndex> IndexVec<T, Index> {
     pub fn new() -> IndexVec<T, Index> {
-        IndexVec { underlying: Vec::new(), marker: std::marker::PhantomData }
+        IndexVec {
+            slots: Vec::new(),
+            free: Vec::new(),
+            marker: std::marker::PhantomData,
+        }
     }
     pub fn push(&mut self, element: T) -> Index {
-        self.underlying.push(element);
-        Index::new(self.underlying.len() - 1)
+        let index = match self.free.pop() {
+            Some(index) => index,
+            None => {
+                self.slots.push(Slot { element: None, generation: 0 });
+                self.slots.len() - 1
+            }
+        };
+        let slot = &mut self.slots[index];
+        slot.element = Some(element);
+        Index::with_generation(index, slot.generation)
+    }
+    pub fn remove(&mut self, index: Index) -> Option<T> {
+        let slot = self.slot_mut(index)?;
+        let element = slot.element.take()?;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(index.get());
+        Some(element)
     }
     pub fn get(&self, index: Index) -> Option<&T> {
-        self.underlying.get(index.get())
+        let slot = self.slots.get(index.get())?;
+        let current = index.generation().is_none_or(|generation| generation == slot.generation);
+        slot.element.as_ref().filter(|_| current)
     }
     pub fn get_mut(&mut self, index: Index) -> Option<&mut T> {
-        self.underlying.get_mut(index.get())
+        self.slot_mut(index)?.element.as_mut()
+    }
+    fn slot_mut(&mut self, index: Index) -> Option<&mut Slot<T>> {
+        let slot = self.slots.get_mut(index.get())?;
+        let current = index.generation().is_none_or(|generation| generation == slot.generation);
+        current.then_some(slot)
     }
+    // The number of elements, not counting removed ones.
     pub fn len(&self) -> usize {
-        self.underlying.len()
+        self.slots.len() - self.free.len()
+    }
+    // The current index of the element in the given slot, if there is one.
+    pub fn index_at(&self, slot: usize) -> Option<Index> {
+        let Slot { element, generation } = self.slots.get(slot)?;
+        element.as_ref().map(|_| Index::with_generation(slot, *generation))
+    }
+    pub fn indices(&self) -> impl Iterator<Item = Index> + '_ {
+        (0..self.slots.len()).filter_map(|slot| self.index_at(slot))
+    }
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.slots.iter_mut().filter_map(|slot| slot.element.as_mut())
     }
 }
 
@@ -57,12 +113,25 @@ macro_rules! define_index {
             fn get(self) -> usize { self.0 }
             fn new(index: usize) -> Self { Self(index) }
         }
-    }
+    };
+    // Indices that remember the generation of their slot, so that they can be detected as stale
+    // once the element is removed, even if the slot has been reused.
+    ($visibility:vis $name:ident, generational) => {
+        #[derive(Clone, Copy, PartialEq, Debug)]
+        $visibility struct $name { index: usize, generation: u32 }
+        impl $crate::indexvec::VecIndex for $name {
+            fn get(self) -> usize { self.index }
+            fn new(index: usize) -> Self { Self { index, generation: 0 } }
+            fn generation(self) -> Option<u32> { Some(self.generation) }
+            fn with_generation(index: usize, generation: u32) -> Self { Self { index, generation } }
+        }
+    };
 }
 
 #[cfg(test)]
 mod tests {
     define_index!(MyIndex);
+    define_index!(Generational, generational);
 
     #[test]
     fn index_vec() {
@@ -70,4 +139,29 @@ mod tests {
         let id: MyIndex = vec.push("hello".to_owned());
         assert_eq!(vec[id], "hello".to_owned());
     }
+    #[test]
+    fn remove() {
+        let mut vec = super::IndexVec::<&str, MyIndex>::default();
+        let a = vec.push("a");
+        let b = vec.push("b");
+        let c = vec.push("c");
+        assert_eq!(vec.remove(b), Some("b"));
+        assert_eq!(vec.remove(b), None);
+        assert_eq!(vec.get(b), None);
+        assert_eq!((vec[a], vec[c], vec.len()), ("a", "c", 2));
+        assert_eq!(vec.indices().collect::<Vec<_>>(), [a, c]);
+        assert_eq!(vec.push("d"), b);
+        assert_eq!(vec.indices().map(|id| vec[id]).collect::<Vec<_>>(), ["a", "d", "c"]);
+    }
+    #[test]
+    fn generation() {
+        let mut vec = super::IndexVec::<&str, Generational>::default();
+        let a = vec.push("a");
+        vec.remove(a);
+        let b = vec.push("b");
+        assert_eq!(super::VecIndex::get(a), super::VecIndex::get(b));
+        assert_eq!((vec.get(a), vec.get(b)), (None, Some(&"b")));
+        assert_eq!(vec.remove(a), None);
+        assert_eq!(vec.index_at(0), Some(b));
+    }
 }
@@ -1,8 +1,13 @@
+mod complete;
 mod editor;
+mod highlight;
+mod history;
 mod indexvec;
+mod search;
 mod settings;
 mod terminal;
 mod text;
+mod textobject;
 mod ui;
 mod util;
 
@@ -0,0 +1,186 @@
+use std::ops::Range;
+
+// A search pattern, matched within single lines. Patterns are regular expressions, or literal
+// text with `noregex`. Like in vim, `\<` and `\>` make the pattern only match at the start and
+// the end of a word.
+#[derive(Clone, Debug)]
+pub enum Pattern<'a> {
+    Literal(Literal<'a>),
+    Regex(regex::Regex),
+}
+
+// A literal pattern, where `\<` can only be at the start and `\>` at the end.
+#[derive(Clone, Copy, Debug)]
+pub struct Literal<'a> {
+    text: &'a str,
+    ignore_case: bool,
+    word_start: bool,
+    word_end: bool,
+}
+
+pub fn is_word_char(character: char) -> bool {
+    character.is_alphanumeric() || character == '_'
+}
+
+fn chars_equal(a: char, b: char, ignore_case: bool) -> bool {
+    a == b || (ignore_case && a.to_lowercase().eq(b.to_lowercase()))
+}
+
+impl<'a> Pattern<'a> {
+    // With `smartcase`, case is only ignored if the pattern has no uppercase letters. Returns a
+    // description of the error if the regular expression is invalid.
+    pub fn new(
+        text: &'a str,
+        ignorecase: bool,
+        smartcase: bool,
+        regex: bool,
+    ) -> Result<Pattern<'a>, String> {
+        let ignore_case = ignorecase && !(smartcase && text.chars().any(char::is_uppercase));
+        if regex {
+            let text = text.replace("\\<", "\\b{start}").replace("\\>", "\\b{end}");
+            return match regex::RegexBuilder::new(&text).case_insensitive(ignore_case).build() {
+                Ok(regex) => Ok(Pattern::Regex(regex)),
+                // The last line of a syntax error describes it, after the pattern and a caret.
+                Err(regex::Error::Syntax(error)) => {
+                    let error = error.lines().last().unwrap_or_default();
+                    Err(format!("Invalid pattern: {}", error.trim_start_matches("error: ")))
+                }
+                Err(error) => Err(format!("Invalid pattern: {error}")),
+            };
+        }
+        let (word_start, text) = match text.strip_prefix("\\<") {
+            Some(text) => (true, text),
+            None => (false, text),
+        };
+        let (word_end, text) = match text.strip_suffix("\\>") {
+            Some(text) => (true, text),
+            None => (false, text),
+        };
+        Ok(Pattern::Literal(Literal { text, ignore_case, word_start, word_end }))
+    }
+
+    // The byte ranges of the non-overlapping matches in the line, in order. Regular expressions
+    // can have empty matches, like `^`, but literal patterns never do.
+    pub fn find_all(&self, line: &str) -> Vec<Range<usize>> {
+        match self {
+            Pattern::Literal(literal) => literal.find_all(line),
+            Pattern::Regex(regex) => regex.find_iter(line).map(|found| found.range()).collect(),
+        }
+    }
+
+    // The line with the first match replaced, or every match with `global`, and the number of
+    // replaced matches. Regular expression replacements can refer to groups with `$1`.
+    pub fn substitute(&self, line: &str, replacement: &str, global: bool) -> (String, usize) {
+        let mut matches = self.find_all(line);
+        matches.truncate(if global { usize::MAX } else { 1 });
+        let replaced = match self {
+            _ if matches.is_empty() => line.to_owned(),
+            Pattern::Literal(_) => {
+                let mut replaced = String::new();
+                let mut end = 0;
+                for range in &matches {
+                    replaced.push_str(&line[end..range.start]);
+                    replaced.push_str(replacement);
+                    end = range.end;
+                }
+                replaced + &line[end..]
+            }
+            Pattern::Regex(regex) => regex.replacen(line, matches.len(), replacement).into_owned(),
+        };
+        (replaced, matches.len())
+    }
+}
+
+impl Literal<'_> {
+    // The end of the match that starts at `start`, if there is one.
+    fn match_at(&self, line: &str, start: usize) -> Option<usize> {
+        if self.word_start && line[..start].chars().next_back().is_some_and(is_word_char) {
+            return None;
+        }
+        let mut characters = line[start..].char_indices();
+        for expected in self.text.chars() {
+            let (_, character) = characters.next()?;
+            if !chars_equal(character, expected, self.ignore_case) {
+                return None;
+            }
+        }
+        match characters.next() {
+            Some((_, next)) if self.word_end && is_word_char(next) => None,
+            Some((index, _)) => Some(start + index),
+            None => Some(line.len()),
+        }
+    }
+
+    fn find_all(&self, line: &str) -> Vec<Range<usize>> {
+        let mut matches = Vec::new();
+        if self.text.is_empty() {
+            return matches;
+        }
+        let mut start = 0;
+        while start < line.len() {
+            match self.match_at(line, start) {
+                Some(end) => {
+                    matches.push(start..end);
+                    start = end;
+                }
+                None => start += line[start..].chars().next().map_or(1, char::len_utf8),
+            }
+        }
+        matches
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Pattern;
+
+    #[test]
+    fn find_all() {
+        let find = |pattern, ignorecase, smartcase, line| -> Vec<(usize, usize)> {
+            let matches =
+                Pattern::new(pattern, ignorecase, smartcase, false).unwrap().find_all(line);
+            matches.into_iter().map(|range| (range.start, range.end)).collect()
+        };
+        assert_eq!(find("ab", false, false, "abAbab"), [(0, 2), (4, 6)]);
+        assert_eq!(find("ab", true, false, "abAbab"), [(0, 2), (2, 4), (4, 6)]);
+        assert_eq!(find("aa", false, false, "aaa"), [(0, 2)]);
+        assert_eq!(find("Ab", true, true, "abAbab"), [(2, 4)]);
+        assert_eq!(find("ab", true, true, "abAbab"), [(0, 2), (2, 4), (4, 6)]);
+        assert_eq!(find("ö", true, false, "äÖö"), [(2, 4), (4, 6)]);
+        assert_eq!(find("", false, false, "abc"), []);
+        assert_eq!(find("\\<ab\\>", false, false, "ab abc cab ab_ ab."), [(0, 2), (15, 17)]);
+        assert_eq!(find("\\<ab", false, false, "ab abc cab"), [(0, 2), (3, 5)]);
+    }
+
+    #[test]
+    fn regex() {
+        let find = |pattern, ignorecase, line| -> Vec<(usize, usize)> {
+            let matches = Pattern::new(pattern, ignorecase, true, true).unwrap().find_all(line);
+            matches.into_iter().map(|range| (range.start, range.end)).collect()
+        };
+        assert_eq!(find("\\d+", false, "a12b3"), [(1, 3), (4, 5)]);
+        assert_eq!(find("\\bab\\b", false, "ab abc"), [(0, 2)]);
+        assert_eq!(find("\\<ab\\>", false, "ab abc cab ab."), [(0, 2), (11, 13)]);
+        assert_eq!(find("ab", true, "aB"), [(0, 2)]);
+        assert_eq!(find("Ab", true, "ab"), []);
+        assert_eq!(find("^", false, "ab"), [(0, 0)]);
+        let error = Pattern::new("a(", false, false, true).unwrap_err();
+        assert_eq!(error, "Invalid pattern: unclosed group");
+    }
+
+    #[test]
+    fn substitute() {
+        let substitute = |pattern, regex, line, replacement, global| {
+            Pattern::new(pattern, false, false, regex).unwrap().substitute(
+                line,
+                replacement,
+                global,
+            )
+        };
+        assert_eq!(substitute("a", false, "banana", "o", false), (String::from("bonana"), 1));
+        assert_eq!(substitute("a", false, "banana", "$1", true), (String::from("b$1n$1n$1"), 3));
+        assert_eq!(substitute("x", false, "banana", "o", true), (String::from("banana"), 0));
+        assert_eq!(substitute("(a)n", true, "banana", "<$1>", true), (String::from("b<a><a>a"), 2));
+        assert_eq!(substitute("^", true, "ab", "# ", true), (String::from("# ab"), 1));
+    }
+}
@@ -1,5 +1,7 @@
 #![allow(clippy::derivable_impls)]
 
+use crossterm::style::Color;
+
 #[derive(Clone, Copy, Debug)]
 pub struct WindowBorders {
     pub top_left: char,
@@ -16,33 +18,109 @@ pub struct WindowBorders {
 pub struct WindowSettings {
     pub borders: WindowBorders,
     pub number: bool,
+    pub autoindent: bool,
+    pub cursorline: bool,
+    // Tabs, trailing spaces, and line ends are shown as visible characters.
+    pub list: bool,
+    // The screen column, counted from 1, that is highlighted as a ruler. 0 disables it.
+    pub colorcolumn: u16,
+    // Side by side windows are separated by a single divider instead of two borders.
+    pub divider: bool,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Theme {
+    pub status_background: Color,
+    pub line_number: Color,
+    pub inactive_border: Color,
+    pub cursor_line: Color,
+    // The cursor of windows that are not focused.
+    pub inactive_cursor: Color,
+    pub color_column: Color,
+    pub selection: Color,
+    pub search_match: Color,
+    pub keyword: Color,
+    pub string: Color,
+    pub comment: Color,
+    pub number: Color,
 }
 
 #[derive(Clone, Copy, Debug)]
 pub struct EditorSettings {
     pub showmode: bool,
+    pub theme: Theme,
+    pub ignorecase: bool,
+    // Searches are case sensitive when the pattern has uppercase letters, if `ignorecase` is set.
+    pub smartcase: bool,
+    pub incsearch: bool,
+    // Search patterns are regular expressions, rather than literal text.
+    pub regex: bool,
+    // Unmodified buffers are reloaded when their files change on disk.
+    pub autoread: bool,
+    // Moving left and right at the start and end of a line continues on the adjacent line.
+    pub whichwrap: bool,
+    // The number of columns kept visible to the left and right of the cursor.
+    pub sidescrolloff: u16,
+    // Splitting a window moves the focus to the new window.
+    pub splitfocus: bool,
+    // The status line is always shown with 2. With 0, its row is used by the windows, and it is
+    // only shown over them for messages and the command line.
+    pub laststatus: u8,
 }
 
 #[derive(Clone, Copy, Debug)]
 pub struct BufferSettings {
+    // Buffers opened with `:view` are not modifiable.
     pub modifiable: bool,
+    pub tabstop: u16,
+    pub expandtab: bool,
+    // Files are written with a final line break, even if the text does not end with one.
+    pub fixendofline: bool,
+    // Trailing whitespace is removed from every line when the buffer is written.
+    pub trim_trailing_whitespace: bool,
 }
 
 impl Default for EditorSettings {
     fn default() -> Self {
-        EditorSettings { showmode: true }
+        EditorSettings {
+            showmode: true,
+            theme: Theme::dark(),
+            ignorecase: false,
+            smartcase: false,
+            incsearch: false,
+            regex: true,
+            autoread: false,
+            whichwrap: false,
+            sidescrolloff: 0,
+            splitfocus: false,
+            laststatus: 2,
+        }
     }
 }
 
 impl Default for BufferSettings {
     fn default() -> Self {
-        BufferSettings { modifiable: false }
+        BufferSettings {
+            modifiable: true,
+            tabstop: 8,
+            expandtab: false,
+            fixendofline: true,
+            trim_trailing_whitespace: false,
+        }
     }
 }
 
 impl Default for WindowSettings {
     fn default() -> Self {
-        WindowSettings { borders: WindowBorders::unicode(), number: true }
+        WindowSettings {
+            borders: WindowBorders::unicode(),
+            number: true,
+            autoindent: true,
+            cursorline: false,
+            list: false,
+            colorcolumn: 0,
+            divider: false,
+        }
     }
 }
 
@@ -74,3 +152,47 @@ impl WindowBorders {
         }
     }
 }
+
+impl Theme {
+    pub fn named(name: &str) -> Option<Theme> {
+        match name {
+            "dark" => Some(Theme::dark()),
+            "light" => Some(Theme::light()),
+            _ => None,
+        }
+    }
+    #[rustfmt::skip]
+    const fn dark() -> Theme {
+        Theme {
+            status_background: Color::DarkGrey,
+            line_number:       Color::DarkGrey,
+            inactive_border:   Color::DarkGrey,
+            cursor_line:       Color::AnsiValue(236),
+            inactive_cursor:   Color::AnsiValue(242),
+            color_column:      Color::AnsiValue(235),
+            selection:         Color::AnsiValue(239),
+            search_match:      Color::AnsiValue(58),
+            keyword:           Color::Magenta,
+            string:            Color::Green,
+            comment:           Color::DarkGrey,
+            number:            Color::Cyan,
+        }
+    }
+    #[rustfmt::skip]
+    const fn light() -> Theme {
+        Theme {
+            status_background: Color::Grey,
+            line_number:       Color::Grey,
+            inactive_border:   Color::Grey,
+            cursor_line:       Color::AnsiValue(254),
+            inactive_cursor:   Color::AnsiValue(249),
+            color_column:      Color::AnsiValue(255),
+            selection:         Color::AnsiValue(251),
+            search_match:      Color::AnsiValue(229),
+            keyword:           Color::DarkMagenta,
+            string:            Color::DarkGreen,
+            comment:           Color::Grey,
+            number:            Color::DarkCyan,
+        }
+    }
+}
@@ -1,14 +1,13 @@
-use crate::util::Direction;
-use crossterm::{cursor, terminal};
+use crossterm::{cursor, event, terminal};
 use std::io;
 
-#[derive(Clone, Copy, Default, Debug)]
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
 pub struct Position {
     pub x: u16,
     pub y: u16,
 }
 
-#[derive(Clone, Copy, Default, Debug)]
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
 pub struct Size {
     pub width: u16,
     pub height: u16,
@@ -18,50 +17,50 @@ pub fn size() -> io::Result<Size> {
     terminal::size().map(|(width, height)| Size { width, height })
 }
 
-pub fn queue(command: impl crossterm::Command) -> io::Result<()> {
-    crossterm::queue!(io::stdout(), command)
+pub fn queue(out: &mut impl io::Write, command: impl crossterm::Command) -> io::Result<()> {
+    crossterm::queue!(out, command)
 }
 
-pub fn flush() -> io::Result<()> {
-    io::Write::flush(&mut io::stdout())
+// Frames are accumulated in memory and written out all at once.
+pub fn flush(frame: &[u8]) -> io::Result<()> {
+    let mut stdout = io::stdout().lock();
+    io::Write::write_all(&mut stdout, frame)?;
+    io::Write::flush(&mut stdout)
 }
 
 pub fn begin() -> io::Result<()> {
     terminal::enable_raw_mode()?;
-    queue(terminal::EnterAlternateScreen)?;
-    queue(terminal::DisableLineWrap)?;
+    queue(&mut io::stdout(), terminal::EnterAlternateScreen)?;
+    queue(&mut io::stdout(), terminal::DisableLineWrap)?;
+    queue(&mut io::stdout(), event::EnableMouseCapture)?;
+    queue(&mut io::stdout(), event::EnableFocusChange)?;
     Ok(())
 }
 
 pub fn end() -> io::Result<()> {
     terminal::disable_raw_mode()?;
-    queue(terminal::LeaveAlternateScreen)?;
-    queue(terminal::EnableLineWrap)?;
-    queue(cursor::Show)?;
+    queue(&mut io::stdout(), event::DisableMouseCapture)?;
+    queue(&mut io::stdout(), event::DisableFocusChange)?;
+    queue(&mut io::stdout(), terminal::LeaveAlternateScreen)?;
+    queue(&mut io::stdout(), terminal::EnableLineWrap)?;
+    queue(&mut io::stdout(), cursor::SetCursorStyle::DefaultUserShape)?;
+    queue(&mut io::stdout(), cursor::Show)?;
     Ok(())
 }
 
-pub fn clear() -> io::Result<()> {
-    queue(terminal::Clear(terminal::ClearType::All))
+pub fn clear(out: &mut impl io::Write) -> io::Result<()> {
+    queue(out, terminal::Clear(terminal::ClearType::All))
 }
 
-pub fn clear_line() -> io::Result<()> {
-    queue(terminal::Clear(terminal::ClearType::CurrentLine))
+pub fn clear_line(out: &mut impl io::Write) -> io::Result<()> {
+    queue(out, terminal::Clear(terminal::ClearType::CurrentLine))
 }
 
-pub fn set_cursor(Position { x, y }: Position) -> io::Result<()> {
-    queue(cursor::MoveTo(x, y))
+pub fn set_cursor(out: &mut impl io::Write, Position { x, y }: Position) -> io::Result<()> {
+    queue(out, cursor::MoveTo(x, y))
 }
 
 impl Position {
-    pub fn move_toward(self, direction: Direction) -> Position {
-        match direction {
-            Direction::Up => Position { y: self.y.saturating_sub(1), ..self },
-            Direction::Down => Position { y: self.y.saturating_add(1), ..self },
-            Direction::Left => Position { x: self.x.saturating_sub(1), ..self },
-            Direction::Right => Position { x: self.x.saturating_add(1), ..self },
-        }
-    }
     pub fn offset_x(self, x: u16) -> Position {
         Position { x: self.x + x, y: self.y }
     }
@@ -1,9 +1,13 @@
 #![allow(dead_code)]
 
+use std::io;
+use std::ops::Range;
+
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+
 // Potential optimizations:
 // - zero-width pieces
 // - single buffer table
-// - avoid find_piece traversal
 
 #[derive(Clone, Copy, Debug)]
 struct Piece {
@@ -12,7 +16,7 @@ struct Piece {
     kind: PieceKind,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, PartialEq, Debug)]
 enum PieceKind {
     Original,
     Append,
@@ -24,11 +28,54 @@ struct PiecePosition {
     relative_offset: usize,
 }
 
+// The most recently found piece and the offset at which it begins.
+#[derive(Clone, Copy, Default, Debug)]
+struct PieceCache {
+    piece_index: usize,
+    piece_offset: usize,
+}
+
+pub struct Chars<'a> {
+    table: &'a PieceTable,
+    pieces: std::slice::Iter<'a, Piece>,
+    current: std::str::Chars<'a>,
+    remaining: usize,
+}
+
+pub struct Bytes<'a> {
+    table: &'a PieceTable,
+    pieces: std::slice::Iter<'a, Piece>,
+    current: std::str::Bytes<'a>,
+    remaining: usize,
+}
+
+// Replaces `width` bytes at `offset` with `text`.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Edit {
+    pub offset: usize,
+    pub width: usize,
+    pub text: String,
+}
+
+// A previous state of the text. Text is never removed from the buffers, so the pieces are enough
+// to restore it.
+#[derive(Clone, Debug)]
+struct Snapshot {
+    pieces: Vec<Piece>,
+    length: usize,
+}
+
 #[derive(Default)]
 pub struct PieceTable {
     original: String,
     append: String,
     pieces: Vec<Piece>,
+    cache: PieceCache,
+    length: usize,
+    undo: Vec<Snapshot>,
+    redo: Vec<Snapshot>,
+    pending: Option<Snapshot>,
+    changed: bool,
 }
 
 impl Piece {
@@ -41,19 +88,37 @@ impl Piece {
         };
         (left, right)
     }
+    fn merge(self, next: Piece) -> Option<Piece> {
+        (self.kind == next.kind && self.offset + self.width == next.offset)
+            .then_some(Piece { width: self.width + next.width, ..self })
+    }
 }
 
 impl PieceTable {
-    fn find_piece(pieces: &[Piece], offset: usize) -> Option<PiecePosition> {
-        let mut current_offset = 0;
-        for (piece_index, piece) in pieces.iter().enumerate() {
-            current_offset += piece.width;
-            if current_offset >= offset {
-                return Some(PiecePosition {
-                    piece_index,
-                    relative_offset: piece.width - (current_offset - offset),
-                });
+    // Scans from the cached piece, so sequential edits at nearby offsets are cheap. An offset
+    // between two pieces is found at the end of the first one, so the end of the table is the end
+    // of the last piece. In a table without pieces, offset 0 is at piece index 0, where the first
+    // piece would go. Offsets past the end are not found.
+    fn find_piece(&mut self, offset: usize) -> Option<PiecePosition> {
+        if self.pieces.is_empty() {
+            self.cache = PieceCache::default();
+            return (offset == 0).then_some(PiecePosition { piece_index: 0, relative_offset: 0 });
+        }
+        let PieceCache { mut piece_index, mut piece_offset } = self.cache;
+        if piece_index >= self.pieces.len() {
+            (piece_index, piece_offset) = (0, 0);
+        }
+        while piece_index != 0 && piece_offset >= offset {
+            piece_index -= 1;
+            piece_offset -= self.pieces[piece_index].width;
+        }
+        while let Some(piece) = self.pieces.get(piece_index) {
+            if piece_offset + piece.width >= offset {
+                self.cache = PieceCache { piece_index, piece_offset };
+                return Some(PiecePosition { piece_index, relative_offset: offset - piece_offset });
             }
+            piece_offset += piece.width;
+            piece_index += 1;
         }
         None
     }
@@ -62,25 +127,64 @@ impl PieceTable {
         self.pieces.splice(from..=to, replacement.filter(|piece| piece.width != 0));
     }
 
+    // Merges neighboring pieces that refer to contiguous text, considering pairs starting in `from..to`.
+    fn merge_pieces(&mut self, from: usize, mut to: usize) {
+        let mut index = from;
+        while index < to && index + 1 < self.pieces.len() {
+            if let Some(merged) = self.pieces[index].merge(self.pieces[index + 1]) {
+                self.pieces[index] = merged;
+                self.pieces.remove(index + 1);
+                to -= 1;
+            }
+            else {
+                index += 1;
+            }
+        }
+    }
+
     fn add_piece(&mut self, string: &str) -> Piece {
         let offset = self.append.len();
         self.append.push_str(string);
         Piece { offset, width: string.len(), kind: PieceKind::Append }
     }
 
+    // Whether the piece ends where the append buffer does, which means it can be extended in place.
+    fn is_extendable(&self, piece: Piece) -> bool {
+        matches!(piece.kind, PieceKind::Append) && piece.offset + piece.width == self.append.len()
+    }
+
     pub fn insert(&mut self, offset: usize, string: &str) {
-        let position = PieceTable::find_piece(&self.pieces, offset).unwrap();
+        if string.is_empty() {
+            return; // Avoid zero-width pieces
+        }
+        let position = self.find_piece(offset).expect("insert offset is past the end of the text");
+        self.length += string.len();
+        self.begin_change();
+        // A new or emptied table, like a scratch buffer, gets its first piece.
+        if self.pieces.is_empty() {
+            let new = self.add_piece(string);
+            self.pieces.push(new);
+            return;
+        }
         let piece = self.pieces[position.piece_index];
+        if position.relative_offset == piece.width && self.is_extendable(piece) {
+            self.append.push_str(string);
+            self.pieces[position.piece_index].width += string.len();
+            return;
+        }
         let new = self.add_piece(string);
         if position.relative_offset == 0 {
             self.pieces.insert(position.piece_index, new);
+            self.cache.piece_index = position.piece_index;
         }
         else if position.relative_offset == piece.width {
             self.pieces.insert(position.piece_index + 1, new);
+            self.cache = PieceCache { piece_index: position.piece_index + 1, piece_offset: offset };
         }
         else {
             let (left, right) = piece.split(position.relative_offset);
             self.splice(position.piece_index, position.piece_index, [left, new, right].into_iter());
+            self.cache = PieceCache { piece_index: position.piece_index + 1, piece_offset: offset };
         }
     }
 
@@ -88,11 +192,128 @@ impl PieceTable {
         if width == 0 {
             return; // Avoid unnecessary piece splitting
         }
-        let start = PieceTable::find_piece(&self.pieces, offset).unwrap();
-        let stop = PieceTable::find_piece(&self.pieces, offset + width).unwrap();
+        let start = self.find_piece(offset).expect("remove offset is past the end of the text");
+        let stop =
+            self.find_piece(offset + width).expect("removed text is past the end of the text");
+        self.length -= width;
+        self.begin_change();
         let (l, _) = self.pieces[start.piece_index].split(start.relative_offset);
         let (_, r) = self.pieces[stop.piece_index].split(stop.relative_offset);
+        let (first, first_offset) = match start.piece_index.checked_sub(1) {
+            Some(previous) => {
+                (previous, offset - start.relative_offset - self.pieces[previous].width)
+            }
+            None => (0, 0),
+        };
         self.splice(start.piece_index, stop.piece_index, [l, r].into_iter());
+        self.merge_pieces(first, start.piece_index + 2);
+        self.cache = PieceCache { piece_index: first, piece_offset: first_offset };
+    }
+
+    // Replaces the range with the string in one splice. Returns the edit that undoes this one.
+    pub fn replace(&mut self, offset: usize, width: usize, string: &str) -> Edit {
+        let removed = self.slice(offset..offset + width);
+        if removed == string {
+            return Edit { offset, width, text: removed };
+        }
+        if width == 0 || string.is_empty() || self.pieces.is_empty() {
+            self.remove(offset, width);
+            self.insert(offset, string);
+            return Edit { offset, width: string.len(), text: removed };
+        }
+        let start = self.find_piece(offset).expect("replace offset is past the end of the text");
+        let stop =
+            self.find_piece(offset + width).expect("replaced text is past the end of the text");
+        self.length = self.length - width + string.len();
+        self.begin_change();
+        let (l, _) = self.pieces[start.piece_index].split(start.relative_offset);
+        let (_, r) = self.pieces[stop.piece_index].split(stop.relative_offset);
+        let new = self.add_piece(string);
+        let (first, first_offset) = match start.piece_index.checked_sub(1) {
+            Some(previous) => {
+                (previous, offset - start.relative_offset - self.pieces[previous].width)
+            }
+            None => (0, 0),
+        };
+        self.splice(start.piece_index, stop.piece_index, [l, new, r].into_iter());
+        self.merge_pieces(first, start.piece_index + 3);
+        self.cache = PieceCache { piece_index: first, piece_offset: first_offset };
+        Edit { offset, width: string.len(), text: removed }
+    }
+
+    // The text in the byte range, which is clamped to the end of the text.
+    pub fn slice(&self, range: Range<usize>) -> String {
+        let (mut string, mut offset) = (String::new(), 0);
+        for &piece in &self.pieces {
+            if offset >= range.end {
+                break;
+            }
+            if offset + piece.width > range.start {
+                let from = range.start.saturating_sub(offset);
+                let to = (range.end - offset).min(piece.width);
+                string.push_str(&self.string_for(piece)[from..to]);
+            }
+            offset += piece.width;
+        }
+        string
+    }
+
+    // Whether the text was changed since the last call.
+    pub fn take_changed(&mut self) -> bool {
+        std::mem::take(&mut self.changed)
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot { pieces: self.pieces.clone(), length: self.length }
+    }
+
+    fn restore(&mut self, snapshot: Snapshot) {
+        (self.pieces, self.length) = (snapshot.pieces, snapshot.length);
+        self.cache = PieceCache::default();
+    }
+
+    // Remembers the current text as the state that `undo` returns to, once the text is changed.
+    // Changes that were undone can no longer be redone after that.
+    pub fn checkpoint(&mut self) {
+        self.pending = Some(self.snapshot());
+    }
+
+    // A checkpoint only becomes an undo step when the text is changed after it.
+    fn begin_change(&mut self) {
+        if let Some(snapshot) = self.pending.take() {
+            self.undo.push(snapshot);
+            self.redo.clear();
+        }
+        self.changed = true;
+    }
+
+    // Returns to the state of the last checkpoint. Returns whether there was one.
+    pub fn undo(&mut self) -> bool {
+        self.pending = None;
+        let Some(snapshot) = self.undo.pop()
+        else {
+            return false;
+        };
+        self.redo.push(self.snapshot());
+        self.restore(snapshot);
+        true
+    }
+
+    // Returns to the state before the last undo. Returns whether there was one.
+    pub fn redo(&mut self) -> bool {
+        self.pending = None;
+        let Some(snapshot) = self.redo.pop()
+        else {
+            return false;
+        };
+        self.undo.push(self.snapshot());
+        self.restore(snapshot);
+        true
+    }
+
+    // The number of changes that can be undone and redone.
+    pub fn undo_counts(&self) -> (usize, usize) {
+        (self.undo.len(), self.redo.len())
     }
 
     fn string_for(&self, piece: Piece) -> &str {
@@ -106,12 +327,195 @@ impl PieceTable {
     pub fn gather(&self) -> String {
         self.pieces.iter().map(|&piece| self.string_for(piece)).collect()
     }
+
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    // Every newline begins a new line, so the last line may be empty.
+    pub fn line_count(&self) -> usize {
+        self.bytes().filter(|&byte| byte == b'\n').count() + 1
+    }
+
+    pub fn line_length(&self, line: usize) -> usize {
+        self.position_of(self.byte_offset_of(line, usize::MAX)).1
+    }
+
+    // The text of the line, without the newline.
+    pub fn line(&self, line: usize) -> String {
+        self.slice(self.byte_offset_of(line, 0)..self.byte_offset_of(line, usize::MAX))
+    }
+
+    pub fn char_at(&self, offset: usize) -> Option<char> {
+        let mut piece_offset = 0;
+        for &piece in &self.pieces {
+            if offset < piece_offset + piece.width {
+                return self.string_for(piece)[offset - piece_offset..].chars().next();
+            }
+            piece_offset += piece.width;
+        }
+        None
+    }
+
+    pub fn chars(&self) -> Chars<'_> {
+        Chars {
+            table: self,
+            pieces: self.pieces.iter(),
+            current: "".chars(),
+            remaining: self.length,
+        }
+    }
+
+    pub fn bytes(&self) -> Bytes<'_> {
+        Bytes {
+            table: self,
+            pieces: self.pieces.iter(),
+            current: "".bytes(),
+            remaining: self.length,
+        }
+    }
+
+    // Columns are counted in chars. Out of range columns are clamped to the end of the line.
+    pub fn byte_offset_of(&self, line: usize, column: usize) -> usize {
+        let (mut offset, mut current_line, mut current_column) = (0, 0, 0);
+        for &piece in &self.pieces {
+            for (index, character) in self.string_for(piece).char_indices() {
+                if current_line == line && (current_column == column || character == '\n') {
+                    return offset + index;
+                }
+                if character == '\n' {
+                    current_line += 1;
+                }
+                else if current_line == line {
+                    current_column += 1;
+                }
+            }
+            offset += piece.width;
+        }
+        offset
+    }
+
+    pub fn position_of(&self, offset: usize) -> (usize, usize) {
+        let (mut line, mut column, mut piece_offset) = (0, 0, 0);
+        for &piece in &self.pieces {
+            for (index, character) in self.string_for(piece).char_indices() {
+                if piece_offset + index >= offset {
+                    return (line, column);
+                }
+                if character == '\n' {
+                    line += 1;
+                    column = 0;
+                }
+                else {
+                    column += 1;
+                }
+            }
+            piece_offset += piece.width;
+        }
+        (line, column)
+    }
+}
+
+impl Iterator for Chars<'_> {
+    type Item = char;
+    fn next(&mut self) -> Option<char> {
+        loop {
+            if let Some(character) = self.current.next() {
+                return Some(character);
+            }
+            let &piece = self.pieces.next()?;
+            self.remaining -= piece.width;
+            self.current = self.table.string_for(piece).chars();
+        }
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let bytes = self.remaining + self.current.as_str().len();
+        (bytes.div_ceil(4), Some(bytes))
+    }
 }
 
+impl Iterator for Bytes<'_> {
+    type Item = u8;
+    fn next(&mut self) -> Option<u8> {
+        loop {
+            if let Some(byte) = self.current.next() {
+                return Some(byte);
+            }
+            let &piece = self.pieces.next()?;
+            self.remaining -= piece.width;
+            self.current = self.table.string_for(piece).bytes();
+        }
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let bytes = self.remaining + self.current.len();
+        (bytes, Some(bytes))
+    }
+}
+
+impl ExactSizeIterator for Bytes<'_> {}
+
 impl From<String> for PieceTable {
     fn from(string: String) -> PieceTable {
         let piece = Piece { offset: 0, width: string.len(), kind: PieceKind::Original };
-        PieceTable { original: string, append: String::new(), pieces: vec![piece] }
+        PieceTable {
+            length: string.len(),
+            original: string,
+            append: String::new(),
+            pieces: vec![piece],
+            cache: PieceCache::default(),
+            undo: Vec::new(),
+            redo: Vec::new(),
+            pending: None,
+            changed: false,
+        }
+    }
+}
+
+// Reads the text in chunks, replacing invalid UTF-8 with U+FFFD.
+// Returns whether anything had to be replaced.
+pub fn read_lossy(mut reader: impl io::Read, size_hint: usize) -> io::Result<(String, bool)> {
+    let mut text = String::with_capacity(size_hint);
+    let mut converted = false;
+    let mut bytes = Vec::new();
+    let mut chunk = vec![0; READ_CHUNK_SIZE];
+    loop {
+        let count = match reader.read(&mut chunk) {
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+            result => result?,
+        };
+        bytes.extend_from_slice(&chunk[..count]);
+        let mut start = 0;
+        while start != bytes.len() {
+            let error = match std::str::from_utf8(&bytes[start..]) {
+                Ok(valid) => {
+                    text.push_str(valid);
+                    start = bytes.len();
+                    break;
+                }
+                Err(error) => error,
+            };
+            let valid_end = start + error.valid_up_to();
+            text.push_str(std::str::from_utf8(&bytes[start..valid_end]).unwrap());
+            start = match error.error_len() {
+                Some(length) => valid_end + length,
+                // An incomplete sequence may be completed by the next chunk.
+                None if count != 0 => {
+                    start = valid_end;
+                    break;
+                }
+                None => bytes.len(),
+            };
+            text.push(char::REPLACEMENT_CHARACTER);
+            converted = true;
+        }
+        bytes.drain(..start);
+        if count == 0 {
+            return Ok((text, converted));
+        }
     }
 }
 
@@ -168,7 +572,7 @@ mod tests {
 
         table.remove(6, 11);
         assert_eq!(table.gather(), "hello, world) end");
-        assert_eq!(table.pieces.len(), 5);
+        assert_eq!(table.pieces.len(), 4);
 
         table.remove(0, 12);
         assert_eq!(table.gather(), ") end");
@@ -189,4 +593,309 @@ mod tests {
         assert_eq!(table.original, "helloworld");
         assert_eq!(table.append, ", ) endbegin ([12589]3467");
     }
+
+    #[test]
+    fn coalesce_inserts() {
+        let mut table: super::PieceTable = "hello".to_owned().into();
+        for (offset, character) in (5..).zip([",", " ", "w", "o", "r", "l", "d"]) {
+            table.insert(offset, character);
+        }
+        assert_eq!(table.gather(), "hello, world");
+        assert_eq!(table.pieces.len(), 2);
+
+        table.insert(5, "!");
+        table.insert(6, "?");
+        assert_eq!(table.gather(), "hello!?, world");
+        assert_eq!(table.pieces.len(), 3);
+
+        table.insert(14, "");
+        table.insert(14, ".");
+        table.insert(7, "x");
+        assert_eq!(table.gather(), "hello!?x, world.");
+        assert_eq!(table.pieces.len(), 5);
+        assert_eq!(table.append, ", world!?.x");
+    }
+
+    #[test]
+    fn merge_after_remove() {
+        let mut table: super::PieceTable = "0123456789".to_owned().into();
+        for offset in [9, 7, 5, 3, 1] {
+            table.insert(offset, "-");
+        }
+        assert_eq!(table.gather(), "0-12-34-56-78-9");
+        assert_eq!(table.pieces.len(), 11);
+        for offset in [1, 3, 5, 7, 9] {
+            table.remove(offset, 1);
+        }
+        assert_eq!(table.gather(), "0123456789");
+        assert_eq!(table.pieces.len(), 1);
+
+        table.remove(2, 2);
+        table.remove(4, 2);
+        assert_eq!(table.gather(), "014589");
+        assert_eq!(table.pieces.len(), 3);
+    }
+
+    #[test]
+    fn insert_at_end() {
+        let mut table = super::PieceTable::default();
+        table.insert(0, "ab");
+        table.insert(2, "c");
+        assert_eq!(table.gather(), "abc");
+        assert_eq!(table.pieces.len(), 1);
+
+        let mut table: super::PieceTable = "ab".to_owned().into();
+        table.insert(2, "c");
+        table.insert(3, "d");
+        table.insert(0, "x");
+        table.insert(5, "e");
+        assert_eq!(table.gather(), "xabcde");
+        assert_eq!(table.len(), 6);
+
+        table.remove(0, 6);
+        table.insert(0, "f");
+        assert_eq!(table.gather(), "f");
+    }
+
+    #[test]
+    fn insert_from_scratch() {
+        let mut table = super::PieceTable::default();
+        let text = "fn main() {\n    println!(\"ö\");\n}\n";
+        for character in text.chars() {
+            table.insert(table.len(), character.encode_utf8(&mut [0; 4]));
+        }
+        assert_eq!(table.gather(), text);
+        assert_eq!(table.len(), text.len());
+        assert_eq!(table.line_count(), 4);
+        assert_eq!(table.pieces.len(), 1);
+    }
+
+    #[test]
+    fn replace() {
+        let mut table: super::PieceTable = "0123456789".to_owned().into();
+        table.insert(5, "abc");
+        assert_eq!(table.gather(), "01234abc56789");
+        let undo = table.replace(3, 4, "xyz");
+        assert_eq!(table.gather(), "012xyzc56789");
+        assert_eq!(undo, super::Edit { offset: 3, width: 3, text: String::from("34ab") });
+        assert_eq!(table.len(), 12);
+
+        let redo = table.replace(undo.offset, undo.width, &undo.text);
+        assert_eq!(table.gather(), "01234abc56789");
+        assert_eq!(redo, super::Edit { offset: 3, width: 4, text: String::from("xyz") });
+
+        table.replace(0, 13, "ö");
+        assert_eq!(table.gather(), "ö");
+        table.replace(2, 0, "!");
+        table.replace(0, 2, "");
+        assert_eq!(table.gather(), "!");
+        assert_eq!(table.len(), 1);
+    }
+
+    #[test]
+    fn slice() {
+        let mut table: super::PieceTable = "hello world".to_owned().into();
+        table.insert(5, ", wörld");
+        table.remove(13, 6);
+        assert_eq!(table.gather(), "hello, wörld");
+        assert_eq!(table.slice(3..10), "lo, wö");
+        assert_eq!(table.slice(5..7), ", ");
+        assert_eq!(table.slice(0..13), "hello, wörld");
+        assert_eq!(table.slice(11..99), "ld");
+        assert_eq!(table.slice(4..4), "");
+        assert_eq!(super::PieceTable::default().slice(0..1), "");
+    }
+
+    #[test]
+    fn undo() {
+        let mut table: super::PieceTable = "abc".to_owned().into();
+        assert!(!table.undo());
+        table.checkpoint();
+        table.insert(3, "d");
+        table.checkpoint();
+        table.remove(0, 2);
+        assert_eq!((table.gather(), table.undo_counts()), (String::from("cd"), (2, 0)));
+        assert!(table.undo());
+        assert_eq!(table.gather(), "abcd");
+        assert!(table.undo());
+        assert_eq!((table.gather(), table.undo_counts()), (String::from("abc"), (0, 2)));
+        assert!(table.redo());
+        assert_eq!(table.gather(), "abcd");
+        table.checkpoint();
+        table.insert(0, "x");
+        assert!(!table.redo());
+        assert_eq!((table.gather(), table.len()), (String::from("xabcd"), 5));
+        assert!(table.undo());
+        assert_eq!((table.gather(), table.len()), (String::from("abcd"), 4));
+        table.checkpoint();
+        table.remove(1, 0);
+        table.replace(0, 1, "a");
+        assert_eq!(table.undo_counts(), (1, 1));
+        assert!(table.redo());
+    }
+
+    #[test]
+    fn len() {
+        let mut table = super::PieceTable::default();
+        assert!(table.is_empty());
+        table = "hello".to_owned().into();
+        assert_eq!(table.len(), 5);
+        table.insert(5, ", wörld");
+        assert_eq!(table.len(), 13);
+        table.remove(1, 4);
+        assert_eq!(table.len(), 9);
+        table.remove(0, 9);
+        assert_eq!(table.len(), 0);
+        assert!(table.is_empty());
+    }
+
+    #[test]
+    fn line_count() {
+        assert_eq!(super::PieceTable::default().line_count(), 1);
+        let mut table: super::PieceTable = "a\nb".to_owned().into();
+        assert_eq!(table.line_count(), 2);
+        table.insert(3, "\n");
+        assert_eq!(table.line_count(), 3);
+    }
+
+    #[test]
+    fn line_length() {
+        let table: super::PieceTable = "abc\n\nöö".to_owned().into();
+        assert_eq!(table.line_length(0), 3);
+        assert_eq!(table.line_length(1), 0);
+        assert_eq!(table.line_length(2), 2);
+        assert_eq!(table.line_length(3), 2);
+    }
+
+    #[test]
+    fn line() {
+        let mut table: super::PieceTable = "ab\ncd\n".to_owned().into();
+        table.insert(4, "ö\nx");
+        assert_eq!(table.gather(), "ab\ncö\nxd\n");
+        assert_eq!(table.line(0), "ab");
+        assert_eq!(table.line(1), "cö");
+        assert_eq!(table.line(2), "xd");
+        assert_eq!(table.line(3), "");
+    }
+
+    #[test]
+    fn char_at() {
+        let mut table: super::PieceTable = "aöb".to_owned().into();
+        table.insert(1, "€");
+        assert_eq!(table.char_at(0), Some('a'));
+        assert_eq!(table.char_at(1), Some('€'));
+        assert_eq!(table.char_at(4), Some('ö'));
+        assert_eq!(table.char_at(6), Some('b'));
+        assert_eq!(table.char_at(7), None);
+    }
+
+    #[test]
+    fn chars_and_bytes() {
+        let mut table: super::PieceTable = "aöb\nc".to_owned().into();
+        table.insert(3, "€");
+        table.insert(0, "x");
+        table.remove(8, 2);
+        assert_eq!(table.gather(), "xaö€b");
+        assert_eq!(table.chars().collect::<String>(), table.gather());
+        assert_eq!(table.bytes().collect::<Vec<u8>>(), table.gather().into_bytes());
+
+        let mut chars = table.chars();
+        assert_eq!(chars.size_hint(), (2, Some(8)));
+        assert_eq!(chars.nth(2), Some('ö'));
+        assert_eq!(chars.size_hint(), (1, Some(4)));
+        assert_eq!(table.bytes().len(), 8);
+        assert_eq!(table.bytes().skip(4).len(), 4);
+        assert_eq!(super::PieceTable::default().chars().next(), None);
+    }
+
+    #[test]
+    fn find_piece_cache() {
+        let mut table: super::PieceTable = "0123456789".to_owned().into();
+        for (offset, string) in [(5, "a"), (6, "b"), (1, "c"), (12, "d"), (7, "e"), (0, "f")] {
+            table.insert(offset, string);
+        }
+        assert_eq!(table.gather(), "f0c1234aeb5678d9");
+        table.remove(14, 2);
+        table.remove(3, 4);
+        table.remove(1, 2);
+        assert_eq!(table.gather(), "faeb5678");
+        table.insert(8, "g");
+        table.insert(0, "h");
+        assert_eq!(table.gather(), "hfaeb5678g");
+    }
+
+    // Run with `cargo test --release -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn bench_sequential_inserts() {
+        let run = |use_cache: bool| {
+            let mut table: super::PieceTable = "x".repeat(30000).into();
+            let start = std::time::Instant::now();
+            // Skip a character between inserts so that they are not coalesced.
+            for offset in (500..).step_by(2).take(10000) {
+                if !use_cache {
+                    table.cache = super::PieceCache::default();
+                }
+                table.insert(offset, "y");
+            }
+            start.elapsed()
+        };
+        let (uncached, cached) = (run(false), run(true));
+        eprintln!("10k sequential inserts: uncached {uncached:?}, cached {cached:?}");
+        assert!(cached < uncached);
+    }
+
+    #[test]
+    fn byte_offset_of() {
+        let mut table: super::PieceTable = "ab\nçd\n\nef".to_owned().into();
+        table.insert(3, "xy");
+        assert_eq!(table.gather(), "ab\nxyçd\n\nef");
+        assert_eq!(table.byte_offset_of(0, 0), 0);
+        assert_eq!(table.byte_offset_of(0, 2), 2);
+        assert_eq!(table.byte_offset_of(0, 9), 2);
+        assert_eq!(table.byte_offset_of(1, 2), 5);
+        assert_eq!(table.byte_offset_of(1, 3), 7);
+        assert_eq!(table.byte_offset_of(2, 0), 9);
+        assert_eq!(table.byte_offset_of(3, 1), 11);
+        assert_eq!(table.byte_offset_of(9, 0), 12);
+    }
+
+    #[test]
+    fn position_of() {
+        let mut table: super::PieceTable = "ab\nçd\n\nef".to_owned().into();
+        table.insert(3, "xy");
+        assert_eq!(table.position_of(0), (0, 0));
+        assert_eq!(table.position_of(2), (0, 2));
+        assert_eq!(table.position_of(3), (1, 0));
+        assert_eq!(table.position_of(5), (1, 2));
+        assert_eq!(table.position_of(7), (1, 3));
+        assert_eq!(table.position_of(9), (2, 0));
+        assert_eq!(table.position_of(12), (3, 2));
+        for (line, column) in [(0, 1), (1, 2), (1, 3), (2, 0), (3, 1)] {
+            assert_eq!(table.position_of(table.byte_offset_of(line, column)), (line, column));
+        }
+    }
+
+    // Yields one byte per read, so that every character is split across reads.
+    struct ByteReader<'a>(&'a [u8]);
+
+    impl std::io::Read for ByteReader<'_> {
+        fn read(&mut self, buffer: &mut [u8]) -> std::io::Result<usize> {
+            let Some((&first, rest)) = self.0.split_first()
+            else {
+                return Ok(0);
+            };
+            (buffer[0], self.0) = (first, rest);
+            Ok(1)
+        }
+    }
+
+    #[test]
+    fn read_lossy() {
+        let read = |bytes| super::read_lossy(ByteReader(bytes), 0).unwrap();
+        assert_eq!(read("aö€😀\n".as_bytes()), ("aö€😀\n".to_owned(), false));
+        assert_eq!(read(b"a\xffb\xe2\x82"), ("a\u{fffd}b\u{fffd}".to_owned(), true));
+        let read = super::read_lossy(&b"x\xe2\x82\xacy"[..], 0).unwrap();
+        assert_eq!(read, ("x€y".to_owned(), false));
+    }
 }
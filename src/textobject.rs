@@ -0,0 +1,201 @@
+use crate::search::is_word_char;
+use std::ops::Range;
+
+// The columns of the text object around `column` in the line, like vim's `iw` and `a(`. Objects
+// are found within the line only, so quotes and brackets must be closed on the same line.
+pub fn find(line: &[char], column: usize, object: char, around: bool) -> Option<Range<usize>> {
+    match object {
+        'w' => word(line, column, around),
+        '"' | '\'' | '`' => quoted(line, column, object, around),
+        '(' | ')' | 'b' => bracketed(line, column, ('(', ')'), around),
+        '[' | ']' => bracketed(line, column, ('[', ']'), around),
+        '{' | '}' | 'B' => bracketed(line, column, ('{', '}'), around),
+        '<' | '>' => bracketed(line, column, ('<', '>'), around),
+        _ => None,
+    }
+}
+
+// The file path at or after `column`, which ends at whitespace, quotes, and brackets.
+pub fn path(line: &[char], column: usize) -> Option<Range<usize>> {
+    let is_path_char =
+        |character: char| !character.is_whitespace() && !"\"'`()[]{}<>,;".contains(character);
+    let column = (column..line.len()).find(|&index| is_path_char(line[index]))?;
+    let start = (0..column).rev().find(|&index| !is_path_char(line[index])).map_or(0, |i| i + 1);
+    let end = (column..line.len()).find(|&index| !is_path_char(line[index])).unwrap_or(line.len());
+    Some(start..end)
+}
+
+// Words are runs of word characters, of other non-blank characters, or of whitespace.
+fn class(character: char) -> u8 {
+    if character.is_whitespace() {
+        0
+    }
+    else if is_word_char(character) {
+        1
+    }
+    else {
+        2
+    }
+}
+
+fn run_end(line: &[char], start: usize) -> usize {
+    let class = class(line[start]);
+    (start..line.len()).find(|&index| self::class(line[index]) != class).unwrap_or(line.len())
+}
+
+fn run_start(line: &[char], end: usize) -> usize {
+    let class = class(line[end - 1]);
+    (0..end).rev().find(|&index| self::class(line[index]) != class).map_or(0, |index| index + 1)
+}
+
+// With `around`, a word includes the whitespace after it, or before it if there is none after.
+// Whitespace includes the word after it.
+fn word(line: &[char], column: usize, around: bool) -> Option<Range<usize>> {
+    let column = column.min(line.len().checked_sub(1)?);
+    let (start, mut end) = (run_start(line, column + 1), run_end(line, column));
+    if !around {
+        return Some(start..end);
+    }
+    if end < line.len() && (class(line[end]) == 0) != (class(line[column]) == 0) {
+        end = run_end(line, end);
+    }
+    else if start > 0 && class(line[column]) != 0 && class(line[start - 1]) == 0 {
+        return Some(run_start(line, start)..end);
+    }
+    Some(start..end)
+}
+
+// The end of `count` words from `column`, including the whitespace after each word, unless
+// `to_end` is set. Whitespace under the cursor counts as a word.
+pub fn words_end(line: &[char], column: usize, count: usize, to_end: bool) -> usize {
+    let mut end = column;
+    for word in 0..count {
+        if to_end && word > 0 && end < line.len() && class(line[end]) == 0 {
+            end = run_end(line, end);
+        }
+        if end < line.len() {
+            end = run_end(line, end);
+        }
+        if !to_end && end < line.len() && class(line[end - 1]) != 0 && class(line[end]) == 0 {
+            end = run_end(line, end);
+        }
+    }
+    end
+}
+
+// Quotes are paired from the start of the line, skipping escaped quotes. If the cursor is not
+// inside a pair, the next pair on the line is used.
+fn quoted(line: &[char], column: usize, quote: char, around: bool) -> Option<Range<usize>> {
+    let quotes: Vec<usize> = (0..line.len())
+        .filter(|&index| line[index] == quote && (index == 0 || line[index - 1] != '\\'))
+        .collect();
+    let (start, end) =
+        quotes.chunks_exact(2).map(|pair| (pair[0], pair[1])).find(|&(_, end)| column <= end)?;
+    Some(if around { start..end + 1 } else { start + 1..end })
+}
+
+// The innermost pair of brackets around the cursor, including brackets under the cursor.
+fn bracketed(
+    line: &[char],
+    column: usize,
+    (open, close): (char, char),
+    around: bool,
+) -> Option<Range<usize>> {
+    let column = column.min(line.len().checked_sub(1)?);
+    let mut depth = 0;
+    let start = (0..=column).rev().find(|&index| {
+        if line[index] == close && index != column {
+            depth += 1;
+        }
+        else if line[index] == open {
+            if depth == 0 {
+                return true;
+            }
+            depth -= 1;
+        }
+        false
+    })?;
+    let end = (start + 1..line.len()).find(|&index| {
+        if line[index] == open {
+            depth += 1;
+        }
+        else if line[index] == close {
+            if depth == 0 {
+                return true;
+            }
+            depth -= 1;
+        }
+        false
+    })?;
+    Some(if around { start..end + 1 } else { start + 1..end })
+}
+
+#[cfg(test)]
+mod tests {
+    fn find(line: &str, column: usize, object: char, around: bool) -> Option<String> {
+        let line: Vec<char> = line.chars().collect();
+        super::find(&line, column, object, around).map(|range| line[range].iter().collect())
+    }
+
+    #[test]
+    fn word() {
+        assert_eq!(find("foo bar.baz", 5, 'w', false).as_deref(), Some("bar"));
+        assert_eq!(find("foo bar.baz", 5, 'w', true).as_deref(), Some(" bar"));
+        assert_eq!(find("foo bar baz", 5, 'w', true).as_deref(), Some("bar "));
+        assert_eq!(find("foo bar", 5, 'w', true).as_deref(), Some(" bar"));
+        assert_eq!(find("foo bar", 3, 'w', false).as_deref(), Some(" "));
+        assert_eq!(find("foo bar", 3, 'w', true).as_deref(), Some(" bar"));
+        assert_eq!(find("a..b", 1, 'w', false).as_deref(), Some(".."));
+        assert_eq!(find("", 0, 'w', false), None);
+    }
+
+    #[test]
+    fn words_end() {
+        let end = |line: &str, column, count, to_end| {
+            super::words_end(&line.chars().collect::<Vec<_>>(), column, count, to_end)
+        };
+        assert_eq!(end("foo bar.baz", 0, 1, false), 4);
+        assert_eq!(end("foo bar.baz", 0, 1, true), 3);
+        assert_eq!(end("foo bar.baz", 0, 2, false), 7);
+        assert_eq!(end("foo bar.baz", 0, 2, true), 7);
+        assert_eq!(end("foo bar.baz", 5, 9, false), 11);
+        assert_eq!(end("foo  bar", 3, 1, true), 5);
+        assert_eq!(end("", 0, 1, false), 0);
+    }
+
+    #[test]
+    fn path() {
+        let path = |line: &str, column| {
+            let line: Vec<char> = line.chars().collect();
+            super::path(&line, column).map(|range| line[range].iter().collect::<String>())
+        };
+        assert_eq!(path("mod \"src/a.rs\";", 8).as_deref(), Some("src/a.rs"));
+        assert_eq!(path("mod \"src/a.rs\";", 0).as_deref(), Some("mod"));
+        assert_eq!(path("see (~/b.txt)", 3).as_deref(), Some("~/b.txt"));
+        assert_eq!(path("x  ", 1), None);
+    }
+
+    #[test]
+    fn quoted() {
+        assert_eq!(find(r#"x = "ab" + "cd""#, 5, '"', false).as_deref(), Some("ab"));
+        assert_eq!(find(r#"x = "ab" + "cd""#, 5, '"', true).as_deref(), Some("\"ab\""));
+        assert_eq!(find(r#"x = "ab" + "cd""#, 0, '"', false).as_deref(), Some("ab"));
+        assert_eq!(find(r#"x = "ab" + "cd""#, 9, '"', false).as_deref(), Some("cd"));
+        assert_eq!(find(r#""a\"b""#, 1, '"', false).as_deref(), Some(r#"a\"b"#));
+        assert_eq!(find(r#"x = "ab""#, 8, '"', false), None);
+        assert_eq!(find("''", 0, '\'', false).as_deref(), Some(""));
+    }
+
+    #[test]
+    fn bracketed() {
+        assert_eq!(find("f(a, (b), c)", 3, '(', false).as_deref(), Some("a, (b), c"));
+        assert_eq!(find("f(a, (b), c)", 3, 'b', true).as_deref(), Some("(a, (b), c)"));
+        assert_eq!(find("f(a, (b), c)", 6, ')', false).as_deref(), Some("b"));
+        assert_eq!(find("f(a, (b), c)", 7, '(', true).as_deref(), Some("(b)"));
+        assert_eq!(find("f(a, (b), c)", 11, '(', false).as_deref(), Some("a, (b), c"));
+        assert_eq!(find("{ [x] }", 3, 'B', false).as_deref(), Some(" [x] "));
+        assert_eq!(find("{ [x] }", 3, '[', false).as_deref(), Some("x"));
+        assert_eq!(find("f(a", 2, '(', false), None);
+        assert_eq!(find("f(a)", 0, '(', false), None);
+    }
+}
@@ -1,123 +1,560 @@
+use crate::complete::Completion;
 use crate::editor;
+use crate::highlight;
+use crate::history::History;
+use crate::settings;
 use crate::terminal::{self, Position};
-use crate::util::Direction;
-use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crate::util::{self, Direction};
+use crossterm::event::{
+    self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEvent,
+    MouseEventKind,
+};
 use crossterm::{cursor, style};
-use std::io;
+use std::io::{self, Write};
+use std::ops::Range;
+use std::path::PathBuf;
+use std::time::Instant;
+
+const COMMAND_HISTORY_CAPACITY: usize = 100;
+
+#[rustfmt::skip]
+const COMMANDS: &[&str] = &[
+    "edit", "view", "enew", "new", "write", "wall", "recover", "file", "bdelete", "quit", "qall",
+    "xit", "xall", "split", "vsplit", "close", "only", "set", "redraw", "help", "nohlsearch",
+    "tabopen", "tabclose", "tabmove", "tabnext", "tabprevious", "TrimWhitespace", "earlier",
+    "later", "undolist", "registers", "sort", "global", "vglobal", "substitute", "Explore",
+];
+
+const HELP: &str = "\
+tek help, close with q
+
+Normal mode
+  h j k l          move the cursor
+  i a A I R        enter insert mode, or replace mode with R
+  x J .            delete characters, join lines, repeat the last change
+  ~                swap the case of characters
+  r{char}          replace characters, or split the line with Enter
+  u Ctrl-r         undo and redo changes, where each insert is one change
+  dd cc yy         delete, change, or yank lines
+  p P \"a           paste after or before the cursor or line, or select the register a
+  >> <<            indent or dedent lines
+  dw d$ diw da(    delete to a motion or a text object, also with c and y
+  D C              delete or change to the end of the line, like d$ and c$
+  f F t T ; ,      find a character on the line, and repeat the find
+  / ? n N * #      search, and search for the word under the cursor
+  % gg G           go to the matching bracket, or the first or last line
+  ma `a            set and jump to a mark
+  Ctrl-o Ctrl-i    jump back and forward in the jump list
+  gf               edit the file under the cursor
+  gt gT H L        go to the next or previous tab
+  Ctrl-g           show the file name and the cursor position
+  Ctrl-v           select a block, then I A insert on each line, and d deletes it
+  Ctrl-w           enter window mode
+  Ctrl-t Ctrl-q    open and close a tab
+  Ctrl-c           quit
+
+Window mode, until Esc
+  h j k l          move the focus
+  H J K L          move the window to an edge
+  + - > <          resize the window
+  s v              split the window, with a count for the new height or width
+  q c o x = w W    close, only, swap, balance, and cycle windows
+
+Commands
+  :e :vie :ene :new    edit a file, read-only, or a new buffer
+  :w :wa :wq :x :xa    write buffers, and quit
+  :q :qa               quit, with ! to discard changes
+  :sp :vs :clo :on     split and close windows, and :10sp sets the new height
+  :bd :f :rec          delete a buffer, show file info, recover a swap file
+  :tabopen :tabclose :tabmove :tabnext :tabprevious
+  :set :noh :redraw :help :{line} :$
+  :reg                 list the registers
+  :sort :sort!         sort lines, or the selection, with u unique and n numeric
+  :g/pat/d :v/pat/d    delete the lines that match a pattern, or that do not
+  :s/pat/rep/g         substitute on the line, or every line with :%s, and $1 for groups
+  :set noregex         search for literal text instead of regular expressions
+  :set laststatus=0    hide the status line, or show it again with laststatus=2
+  :Ex :e .             list a directory, where Enter opens the entry on the line
+  :TrimWhitespace      remove trailing whitespace
+  :ea :lat :undol      undo or redo a number of changes, and count the changes";
+
+// Commands whose argument is a file path.
+#[rustfmt::skip]
+const PATH_COMMANDS: &[&str] = &[
+    "e", "edit", "vie", "view", "w", "write", "wq", "x", "xit", "sp", "split", "vs", "vsp", "vsplit",
+    "Ex", "Explore",
+];
 
 pub struct UI {
     editor: editor::Editor,
     command_line: String,
+    command_cursor: usize,
+    command_history: History,
+    search_history: History,
+    // The character that started the command line: `:` for commands, `/` or `?` for searches.
+    command_prefix: char,
+    completion: Option<Completion>,
+    count: Option<usize>,
+    prefix: Option<char>,
+    // An operator and whether its text object is an `a` object, waiting for the object.
+    pending_object: Option<(editor::Operator, bool)>,
+    // A register selected with `"`, for the command that follows.
+    register: Option<char>,
+    // The lines that were selected when the command line was last opened from visual block mode.
+    visual_lines: Option<Range<usize>>,
+    last_status_line: String,
+    // Whether the status line was last drawn over the windows.
+    status_overlay: bool,
+    cursor_line: Option<(editor::WindowID, u16)>,
+    visual_block: Option<(Range<usize>, Range<usize>)>,
+    frame: Vec<u8>,
+    clear: bool,
     quit: bool,
 }
 
-fn draw_status_line(ui: &UI) -> io::Result<()> {
-    terminal::set_cursor(Position { x: 0, y: ui.editor.size.height })?;
-    terminal::queue(style::SetBackgroundColor(style::Color::DarkGrey))?;
-    terminal::clear_line()?;
+// Keeps the end of the string, since that is the most specific part of a file name.
+fn truncate_start(string: &str, max_width: usize) -> String {
+    let width = string.chars().count();
+    if width <= max_width {
+        return string.to_owned();
+    }
+    let skip = width - max_width.saturating_sub(1);
+    std::iter::once('<').chain(string.chars().skip(skip)).take(max_width).collect()
+}
+
+// Cuts the end of the string off with an ellipsis if it is too wide.
+fn truncate_end(string: &str, max_width: usize) -> String {
+    if string.chars().count() <= max_width {
+        return string.to_owned();
+    }
+    let kept = string.chars().take(max_width.saturating_sub(1));
+    kept.chain(std::iter::once('…')).take(max_width).collect()
+}
+
+// How far through the file the cursor is, like the end of vim's ruler.
+fn ruler_position(line: usize, lines: usize) -> String {
+    if lines <= 1 {
+        String::from("All")
+    }
+    else if line == 0 {
+        String::from("Top")
+    }
+    else if line + 1 >= lines {
+        String::from("Bot")
+    }
+    else {
+        format!("{}%", (line + 1) * 100 / lines)
+    }
+}
+
+// The registers as listed by `:registers`, with whether each one is linewise or characterwise,
+// and the start of its text with line breaks and tabs made visible.
+fn register_list(editor: &editor::Editor) -> String {
+    use std::fmt::Write;
+    let mut names: Vec<char> = editor.registers.keys().copied().collect();
+    names.sort_unstable();
+    let mut list = String::from("Name  Type  Content");
+    for name in names {
+        let register = &editor.registers[&name];
+        let kind = if register.linewise { 'l' } else { 'c' };
+        let content = register.text.replace('\n', "^J").replace('\t', "^I");
+        write!(list, "\n\"{name}    {kind}     {}", truncate_end(&content, 60)).unwrap();
+    }
+    list
+}
 
+fn status_line(ui: &UI) -> String {
+    use std::fmt::Write;
+    let mut line = String::new();
     if ui.editor.mode == editor::Mode::CommandLine {
-        print!(":{} ", ui.command_line);
+        write!(line, "{}{} ", ui.command_prefix, ui.command_line).unwrap();
     }
     else {
         if ui.editor.settings.showmode {
-            print!("-- {:?} -- ", ui.editor.mode);
+            write!(line, "-- {:?} -- ", ui.editor.mode).unwrap();
         }
         if let Some(string) = &ui.editor.status {
-            print!("{string} ");
+            write!(line, "{string} ").unwrap();
+        }
+    }
+    let window = &ui.editor.windows[ui.editor.window_focus()];
+    let buffer = window.view.map(|view| &ui.editor.buffers[view.buffer]);
+    match buffer.and_then(|buffer| Some((buffer, buffer.file_info.as_ref()?))) {
+        Some((buffer, file_info)) => {
+            let name = truncate_start(&file_info.name(), ui.editor.size.width as usize / 3);
+            write!(line, "{name} ").unwrap();
+            if let Some(file_type) = file_info.file_type() {
+                write!(line, "[{file_type}] ").unwrap();
+            }
+            write!(line, "[{}] ", buffer.line_ending.name()).unwrap();
+        }
+        None => {
+            if let Some(directory) = buffer.and_then(|buffer| buffer.directory.as_ref()) {
+                let width = ui.editor.size.width as usize / 3;
+                write!(line, "{} ", truncate_start(&directory.display().to_string(), width))
+                    .unwrap();
+            }
+            let name = buffer.and_then(|buffer| buffer.scratch).unwrap_or("No Name");
+            write!(line, "[{name}] ").unwrap();
         }
     }
-    print!("tab:{}/{} ", ui.editor.current_tab + 1, ui.editor.tabs.len());
-    let cursor = ui.editor.windows[ui.editor.window_focus()].cursor;
-    print!("cursor:{},{} ", cursor.x + 1, cursor.y + 1);
+    write!(line, "tab:{}/{} ", ui.editor.current_tab + 1, ui.editor.tabs.len()).unwrap();
+    write!(line, "cursor:{},{} ", window.cursor.x + 1, window.cursor.y + 1).unwrap();
+    // The position is aligned to the right edge of the screen, and the rest of the line is
+    // truncated to leave room for it.
+    let position = buffer
+        .map_or(String::new(), |buffer| ruler_position(window.cursor.y as usize, buffer.lines()));
+    let width = ui.editor.size.width as usize;
+    let available = width.saturating_sub(position.chars().count());
+    if line.chars().count() > available {
+        line = truncate_end(&line, available.saturating_sub(1));
+    }
+    line.extend(std::iter::repeat_n(' ', available.saturating_sub(line.chars().count())));
+    line.push_str(&position);
+    truncate_end(&line, width)
+}
 
-    terminal::queue(style::SetBackgroundColor(style::Color::Reset))
+// The status line is only drawn when its contents have changed since the last time.
+fn draw_status_line(ui: &mut UI, out: &mut impl Write) -> io::Result<()> {
+    let line = status_line(ui);
+    if line == ui.last_status_line {
+        return Ok(());
+    }
+    terminal::set_cursor(out, Position { x: 0, y: ui.editor.size.height.saturating_sub(1) })?;
+    terminal::queue(out, style::SetBackgroundColor(ui.editor.settings.theme.status_background))?;
+    terminal::clear_line(out)?;
+    write!(out, "{line}")?;
+    ui.last_status_line = line;
+    terminal::queue(out, style::SetBackgroundColor(style::Color::Reset))
 }
 
-fn line_view(line: &str, view: editor::View) -> &str {
-    let from = view.offset as usize;
-    let to = from + view.size.width as usize;
-    line.get(from..to).unwrap_or(line)
+// The cells of the line from the screen column `offset`, with the byte index of the character in
+// each cell. Tabs are expanded to spaces. With `list`, tabs start with an arrow, trailing spaces
+// are dots, and the end of the line is marked in the cell after it.
+fn line_view(
+    line: &str,
+    offset: usize,
+    width: usize,
+    tabstop: u16,
+    list: bool,
+) -> impl Iterator<Item = (usize, char)> + '_ {
+    let trailing = line.trim_end_matches([' ', '\t']).len();
+    let mut column = 0;
+    line.char_indices()
+        .flat_map(move |(index, character)| {
+            let (cell, width) = match character {
+                '\t' => (' ', util::next_tab_stop(column, tabstop) - column),
+                ' ' if list && index >= trailing => ('·', 1),
+                _ => (character, 1),
+            };
+            column += width;
+            let first = if list && character == '\t' { '→' } else { cell };
+            std::iter::once((index, first)).chain(std::iter::repeat_n((index, cell), width - 1))
+        })
+        .chain(list.then_some((line.len(), '¶')))
+        .skip(offset)
+        .take(width)
+}
+
+fn token_color(theme: &settings::Theme, token: highlight::Token) -> style::Color {
+    match token {
+        highlight::Token::Keyword => theme.keyword,
+        highlight::Token::String => theme.string,
+        highlight::Token::Comment => theme.comment,
+        highlight::Token::Number => theme.number,
+    }
 }
 
-fn draw_view(ui: &UI, view: editor::View, position: Position) -> io::Result<()> {
-    let text: String = ui.editor.buffers[view.buffer].text.gather();
-    let lines: Vec<&str> = text.lines().collect();
-    let number_width = lines.len().to_string().len();
-    for (index, &line) in lines.iter().enumerate() {
-        terminal::set_cursor(position.offset_y(index as u16))?;
-        terminal::queue(style::SetForegroundColor(style::Color::DarkGrey))?;
-        terminal::queue(style::SetAttribute(style::Attribute::Bold))?;
-        print!("{:number_width$}", index + 1);
-        terminal::queue(style::SetAttribute(style::Attribute::Reset))?;
-        terminal::queue(style::SetForegroundColor(style::Color::Reset))?;
-        print!(" {}", line_view(line, view));
+// Draws the cells of the line, colored by syntax, with search matches on a distinct background.
+fn draw_line(
+    out: &mut impl Write,
+    theme: &settings::Theme,
+    line: &str,
+    cells: impl Iterator<Item = (usize, char)>,
+    highlighter: Option<&dyn highlight::Highlighter>,
+    matches: &[Range<usize>],
+    background: style::Color,
+) -> io::Result<()> {
+    let spans = highlighter.map(|highlighter| highlighter.highlight_line(line)).unwrap_or_default();
+    let (mut current, mut matched) = (None, false);
+    for (index, character) in cells {
+        let token = spans.iter().find(|span| span.range.contains(&index)).map(|span| span.token);
+        if token != current {
+            terminal::queue(
+                out,
+                style::SetForegroundColor(
+                    token.map_or(style::Color::Reset, |token| token_color(theme, token)),
+                ),
+            )?;
+            current = token;
+        }
+        if matches.iter().any(|range| range.contains(&index)) != matched {
+            matched = !matched;
+            let color = if matched { theme.search_match } else { background };
+            terminal::queue(out, style::SetBackgroundColor(color))?;
+        }
+        write!(out, "{character}")?;
+    }
+    if current.is_some() {
+        terminal::queue(out, style::SetForegroundColor(style::Color::Reset))?;
+    }
+    if matched {
+        terminal::queue(out, style::SetBackgroundColor(background))?;
     }
     Ok(())
 }
 
-fn draw_horizontal_bar(left: char, right: char, middle: char, width: u16) -> io::Result<()> {
-    print!("{left}");
-    for _ in 0..width - 2 {
-        print!("{middle}");
+fn draw_view(
+    ui: &UI,
+    out: &mut impl Write,
+    window: &editor::Window,
+    view: editor::View,
+    focus: bool,
+) -> io::Result<()> {
+    let buffer = &ui.editor.buffers[view.buffer];
+    let text: String = buffer.text.gather();
+    let number_width = window.gutter_width(buffer).saturating_sub(1) as usize;
+    let text_width = window.size.width.saturating_sub(2 + window.gutter_width(buffer)) as usize;
+    let highlighter = (buffer.file_info.as_ref())
+        .and_then(editor::FileInfo::file_type)
+        .and_then(highlight::for_file_type);
+    let position = window.position.offset_x(1).offset_y(1);
+    let height = window.size.height.saturating_sub(2) as usize;
+    let lines = text.split('\n').enumerate().skip(window.scroll_y as usize).take(height);
+    let cursor_line = (focus && window.settings.cursorline).then_some(window.cursor.y as usize);
+    let pattern = ui.editor.highlighted_pattern();
+    let visual_block = ui.editor.visual_block().filter(|_| focus);
+    for (row, (index, line)) in lines.enumerate() {
+        let background =
+            (cursor_line == Some(index)).then_some(ui.editor.settings.theme.cursor_line);
+        terminal::set_cursor(out, position.offset_y(row as u16))?;
+        if let Some(color) = background {
+            terminal::queue(out, style::SetBackgroundColor(color))?;
+            write!(out, "{:1$}", "", window.size.width.saturating_sub(2) as usize)?;
+            terminal::set_cursor(out, position.offset_y(row as u16))?;
+        }
+        if window.settings.number {
+            terminal::queue(out, style::SetForegroundColor(ui.editor.settings.theme.line_number))?;
+            terminal::queue(out, style::SetAttribute(style::Attribute::Bold))?;
+            write!(out, "{:number_width$} ", index + 1)?;
+            terminal::queue(out, style::SetAttribute(style::Attribute::Reset))?;
+            terminal::queue(out, style::SetForegroundColor(style::Color::Reset))?;
+        }
+        if let Some(color) = background {
+            terminal::queue(out, style::SetBackgroundColor(color))?;
+        }
+        let matches = pattern.as_ref().map(|pattern| pattern.find_all(line)).unwrap_or_default();
+        draw_line(
+            out,
+            &ui.editor.settings.theme,
+            line,
+            line_view(
+                line,
+                window.scroll_x as usize,
+                text_width,
+                buffer.settings.tabstop,
+                window.settings.list,
+            ),
+            highlighter,
+            &matches,
+            background.unwrap_or(style::Color::Reset),
+        )?;
+        if background.is_some() {
+            terminal::queue(out, style::SetBackgroundColor(style::Color::Reset))?;
+        }
+        let color_column = (window.settings.colorcolumn as usize)
+            .checked_sub(1 + window.scroll_x as usize)
+            .filter(|&x| x < text_width);
+        if let Some(x) = color_column {
+            let cell = cell_at(line, x + window.scroll_x as usize, window, buffer);
+            let position = position.offset_x(window.gutter_width(buffer) + x as u16);
+            let color = ui.editor.settings.theme.color_column;
+            paint_cell(out, position.offset_y(row as u16), cell, color)?;
+        }
+        if let Some((_, _, columns)) =
+            visual_block.as_ref().filter(|(_, lines, _)| lines.contains(&index))
+        {
+            for column in ui.editor.block_columns(view.buffer, index, columns) {
+                let x = ui.editor.display_column(view.buffer, index, column);
+                let Some(x) = x.checked_sub(window.scroll_x as usize).filter(|&x| x < text_width)
+                else {
+                    continue;
+                };
+                let cell = cell_at(line, x + window.scroll_x as usize, window, buffer);
+                let position = position.offset_x(window.gutter_width(buffer) + x as u16);
+                let color = ui.editor.settings.theme.selection;
+                paint_cell(out, position.offset_y(row as u16), cell, color)?;
+            }
+        }
+    }
+    if !focus {
+        draw_inactive_cursor(ui, out, window, view, &text)?;
     }
-    print!("{right}");
     Ok(())
 }
 
-fn draw_window(ui: &UI, window: &editor::Window, focus: bool) -> io::Result<()> {
+// Windows that are not focused show where their cursor is, since the terminal cursor is elsewhere.
+fn draw_inactive_cursor(
+    ui: &UI,
+    out: &mut impl Write,
+    window: &editor::Window,
+    view: editor::View,
+    text: &str,
+) -> io::Result<()> {
+    let buffer = &ui.editor.buffers[view.buffer];
+    let text_width = window.size.width.saturating_sub(2 + window.gutter_width(buffer));
+    let height = window.size.height.saturating_sub(2);
+    let (line, column) = (window.cursor.y as usize, window.cursor.x as usize);
+    let x = ui.editor.display_column(view.buffer, line, column);
+    let (Some(x), Some(y)) = (
+        (x as u16).checked_sub(window.scroll_x).filter(|&x| x < text_width),
+        window.cursor.y.checked_sub(window.scroll_y).filter(|&y| y < height),
+    )
+    else {
+        return Ok(());
+    };
+    let line = text.split('\n').nth(line).unwrap_or_default();
+    let cell = cell_at(line, x as usize + window.scroll_x as usize, window, buffer);
+    let origin = window.position.offset_x(1 + window.gutter_width(buffer)).offset_y(1);
+    let color = ui.editor.settings.theme.inactive_cursor;
+    paint_cell(out, origin.offset_x(x).offset_y(y), cell, color)
+}
+
+// The character shown at the screen column of the line, or a space past its end.
+fn cell_at(line: &str, column: usize, window: &editor::Window, buffer: &editor::Buffer) -> char {
+    line_view(line, column, 1, buffer.settings.tabstop, window.settings.list)
+        .next()
+        .map_or(' ', |(_, character)| character)
+}
+
+fn paint_cell(
+    out: &mut impl Write,
+    position: Position,
+    cell: char,
+    color: style::Color,
+) -> io::Result<()> {
+    terminal::set_cursor(out, position)?;
+    terminal::queue(out, style::SetBackgroundColor(color))?;
+    write!(out, "{cell}")?;
+    terminal::queue(out, style::SetBackgroundColor(style::Color::Reset))
+}
+
+fn draw_horizontal_bar(
+    out: &mut impl Write,
+    left: char,
+    right: char,
+    middle: char,
+    width: u16,
+) -> io::Result<()> {
+    match width {
+        0 => Ok(()),
+        1 => write!(out, "{left}"),
+        _ => {
+            write!(out, "{left}")?;
+            for _ in 0..width - 2 {
+                write!(out, "{middle}")?;
+            }
+            write!(out, "{right}")
+        }
+    }
+}
+
+fn draw_window(
+    ui: &UI,
+    out: &mut impl Write,
+    window: &editor::Window,
+    focus: bool,
+) -> io::Result<()> {
+    if window.size.width == 0 || window.size.height == 0 {
+        return Ok(());
+    }
     if !focus {
-        terminal::queue(style::SetForegroundColor(style::Color::DarkGrey))?;
+        terminal::queue(out, style::SetForegroundColor(ui.editor.settings.theme.inactive_border))?;
+    }
+    let mut borders = window.settings.borders;
+    if window.settings.divider {
+        // The right border of the window on the left is the divider, and the corners between the
+        // windows become part of the bars, so the windows look like one frame.
+        if window.position.x > 0 {
+            (borders.top_left, borders.bottom_left) = (borders.top_bar, borders.bottom_bar);
+            borders.left_bar = ' ';
+        }
+        if window.position.x + window.size.width < ui.editor.size.width {
+            (borders.top_right, borders.bottom_right) = (borders.top_bar, borders.bottom_bar);
+        }
     }
-    terminal::set_cursor(window.position)?;
+    terminal::set_cursor(out, window.position)?;
     draw_horizontal_bar(
-        window.settings.borders.top_left,
-        window.settings.borders.top_right,
-        window.settings.borders.top_bar,
+        out,
+        borders.top_left,
+        borders.top_right,
+        borders.top_bar,
         window.size.width,
     )?;
-    terminal::set_cursor(window.position.offset_y(window.size.height - 1))?;
+    terminal::set_cursor(out, window.position.offset_y(window.size.height - 1))?;
     draw_horizontal_bar(
-        window.settings.borders.bottom_left,
-        window.settings.borders.bottom_right,
-        window.settings.borders.bottom_bar,
+        out,
+        borders.bottom_left,
+        borders.bottom_right,
+        borders.bottom_bar,
         window.size.width,
     )?;
     for y in 1..window.size.height - 1 {
-        terminal::set_cursor(window.position.offset_y(y))?;
-        draw_horizontal_bar(
-            window.settings.borders.left_bar,
-            window.settings.borders.right_bar,
-            ' ',
-            window.size.width,
-        )?;
+        terminal::set_cursor(out, window.position.offset_y(y))?;
+        draw_horizontal_bar(out, borders.left_bar, borders.right_bar, ' ', window.size.width)?;
     }
     if !focus {
-        terminal::queue(style::SetForegroundColor(style::Color::Reset))?;
+        terminal::queue(out, style::SetForegroundColor(style::Color::Reset))?;
     }
     if let Some(view) = window.view {
-        draw_view(ui, view, window.position.offset_x(1).offset_y(1))?;
+        draw_view(ui, out, window, view, focus)?;
     }
     Ok(())
 }
 
-fn draw_windows(ui: &mut UI) -> io::Result<()> {
+fn draw_windows(ui: &mut UI, out: &mut impl Write) -> io::Result<()> {
     let window_focus = ui.editor.window_focus();
+    let window = &ui.editor.windows[window_focus];
+    let cursor_line = window.settings.cursorline.then_some((window_focus, window.cursor.y));
+    if cursor_line != ui.cursor_line {
+        for (id, _) in ui.cursor_line.into_iter().chain(cursor_line) {
+            ui.editor.windows[id].redraw = true;
+        }
+        ui.cursor_line = cursor_line;
+    }
+    let visual_block = ui.editor.visual_block().map(|(_, lines, columns)| (lines, columns));
+    if visual_block != ui.visual_block {
+        ui.editor.windows[window_focus].redraw = true;
+        ui.visual_block = visual_block;
+    }
+    // Without a status line row, the status line is drawn over the windows while it has
+    // something to show, and the windows are redrawn when it goes away.
+    let overlay = ui.editor.status_rows() == 0
+        && (ui.editor.mode == editor::Mode::CommandLine || ui.editor.status.is_some());
+    if std::mem::replace(&mut ui.status_overlay, overlay) && !overlay {
+        ui.editor.force_redraw();
+    }
     for &id in &ui.editor.tabs[ui.editor.current_tab].open_windows {
         if ui.editor.windows[id].redraw {
-            draw_window(ui, &ui.editor.windows[id], window_focus == id)?;
+            draw_window(ui, out, &ui.editor.windows[id], window_focus == id)?;
             ui.editor.windows[id].redraw = false;
         }
     }
-    draw_status_line(ui)
+    if overlay {
+        ui.last_status_line.clear();
+    }
+    if overlay || ui.editor.status_rows() != 0 {
+        draw_status_line(ui, out)?;
+    }
+    ui.editor.status_shown = true;
+    Ok(())
 }
 
 fn compute_current_cursor(ui: &UI) -> Position {
     if ui.editor.mode == editor::Mode::CommandLine {
+        let column = ui.command_line[..ui.command_cursor].chars().count();
         Position {
-            x: ui
-                .command_line
-                .len()
+            x: column
                 .try_into()
                 .unwrap_or(ui.editor.size.width.saturating_sub(1))
                 .saturating_add(1),
@@ -125,54 +562,547 @@ fn compute_current_cursor(ui: &UI) -> Position {
         }
     }
     else {
-        let window = &ui.editor.windows[ui.editor.tabs[ui.editor.current_tab].window_focus];
-        window.position.offset(window.cursor)
+        ui.editor.screen_cursor(ui.editor.window_focus())
+    }
+}
+
+fn cursor_style(mode: editor::Mode) -> cursor::SetCursorStyle {
+    match mode {
+        editor::Mode::Normal | editor::Mode::Window | editor::Mode::VisualBlock => {
+            cursor::SetCursorStyle::SteadyBlock
+        }
+        editor::Mode::Insert | editor::Mode::CommandLine => cursor::SetCursorStyle::SteadyBar,
+        editor::Mode::Replace => cursor::SetCursorStyle::SteadyUnderScore,
     }
 }
 
 fn draw(ui: &mut UI) -> io::Result<()> {
-    terminal::queue(cursor::Hide)?;
-    draw_windows(ui)?;
-    terminal::set_cursor(compute_current_cursor(ui))?;
-    terminal::queue(cursor::Show)?;
-    terminal::flush()
+    let mut frame = std::mem::take(&mut ui.frame);
+    frame.clear();
+    terminal::queue(&mut frame, cursor::Hide)?;
+    if ui.editor.too_small() {
+        force_redraw(ui);
+        terminal::clear(&mut frame)?;
+        terminal::set_cursor(&mut frame, Position::default())?;
+        let message: String =
+            "Terminal too small".chars().take(ui.editor.size.width as usize).collect();
+        write!(frame, "{message}")?;
+        terminal::flush(&frame)?;
+        ui.frame = frame;
+        return Ok(());
+    }
+    if std::mem::take(&mut ui.clear) {
+        terminal::clear(&mut frame)?;
+    }
+    draw_windows(ui, &mut frame)?;
+    terminal::set_cursor(&mut frame, compute_current_cursor(ui))?;
+    terminal::queue(&mut frame, cursor_style(ui.editor.mode))?;
+    if ui.editor.mode == editor::Mode::CommandLine
+        || ui.editor.cursor_visible(ui.editor.window_focus())
+    {
+        terminal::queue(&mut frame, cursor::Show)?;
+    }
+    terminal::flush(&frame)?;
+    ui.frame = frame;
+    Ok(())
 }
 
-fn execute_command_line(ui: &mut UI) -> io::Result<()> {
-    let mut pieces = ui.command_line.split_whitespace();
-    if let Some(command) = pieces.next() {
+fn force_redraw(ui: &mut UI) {
+    ui.editor.force_redraw();
+    ui.last_status_line.clear();
+    ui.clear = true;
+}
+
+fn command_history(ui: &mut UI) -> &mut History {
+    match ui.command_prefix {
+        ':' => &mut ui.command_history,
+        _ => &mut ui.search_history,
+    }
+}
+
+fn set_command_line(ui: &mut UI, command_line: String) {
+    ui.command_line = command_line;
+    ui.command_cursor = ui.command_line.len();
+}
+
+// Applies one argument of `:set`, which is either `name=value`, `name`, or `noname`.
+fn set_option(ui: &mut UI, argument: &str) {
+    let id = ui.editor.window_focus();
+    let buffer = ui.editor.windows[id].view.map(|view| view.buffer);
+    match argument.split_once('=') {
+        Some(("theme", name)) => match settings::Theme::named(name) {
+            Some(theme) => {
+                ui.editor.settings.theme = theme;
+                force_redraw(ui);
+            }
+            None => ui.editor.emit_message(format!("Unknown theme: {name}")),
+        },
+        Some(("tabstop", value)) => match (value.parse(), buffer) {
+            (Ok(tabstop @ 1..), Some(buffer)) => {
+                ui.editor.buffers[buffer].settings.tabstop = tabstop;
+                ui.editor.windows[id].redraw = true;
+            }
+            _ => ui.editor.emit_message(format!("Invalid tabstop: {value}")),
+        },
+        Some(("colorcolumn", value)) => match value.parse() {
+            Ok(column) => {
+                ui.editor.windows[id].settings.colorcolumn = column;
+                ui.editor.windows[id].redraw = true;
+            }
+            Err(_) => ui.editor.emit_message(format!("Invalid colorcolumn: {value}")),
+        },
+        Some(("laststatus", value)) => match value.parse() {
+            Ok(laststatus @ (0 | 2)) => {
+                ui.editor.set_laststatus(laststatus);
+                force_redraw(ui);
+            }
+            _ => ui.editor.emit_message(format!("Invalid laststatus: {value}")),
+        },
+        Some(("sidescrolloff", value)) => match value.parse() {
+            Ok(columns) => {
+                ui.editor.settings.sidescrolloff = columns;
+                ui.editor.clamp_cursor(id);
+            }
+            Err(_) => ui.editor.emit_message(format!("Invalid sidescrolloff: {value}")),
+        },
+        Some(_) => ui.editor.emit_message(format!("Unknown option: {argument}")),
+        None => {
+            let (name, value) = match argument.strip_prefix("no") {
+                Some(name) => (name, false),
+                None => (argument, true),
+            };
+            let window = &mut ui.editor.windows[id];
+            match (name, buffer) {
+                ("cursorline", _) => window.settings.cursorline = value,
+                ("list", _) => (window.settings.list, window.redraw) = (value, true),
+                ("divider", _) => {
+                    window.settings.divider = value;
+                    ui.editor.force_redraw();
+                }
+                ("number", _) => (window.settings.number, window.redraw) = (value, true),
+                ("autoindent", _) => window.settings.autoindent = value,
+                ("ignorecase", _) => {
+                    ui.editor.settings.ignorecase = value;
+                    ui.editor.force_redraw();
+                }
+                ("incsearch", _) => ui.editor.settings.incsearch = value,
+                ("regex", _) => {
+                    ui.editor.settings.regex = value;
+                    ui.editor.force_redraw();
+                }
+                ("autoread", _) => ui.editor.settings.autoread = value,
+                ("whichwrap", _) => ui.editor.settings.whichwrap = value,
+                ("splitfocus", _) => ui.editor.settings.splitfocus = value,
+                ("smartcase", _) => {
+                    ui.editor.settings.smartcase = value;
+                    ui.editor.force_redraw();
+                }
+                ("modifiable", Some(buffer)) => {
+                    ui.editor.buffers[buffer].settings.modifiable = value
+                }
+                ("expandtab", Some(buffer)) => ui.editor.buffers[buffer].settings.expandtab = value,
+                ("fixendofline" | "fixeol", Some(buffer)) => {
+                    ui.editor.buffers[buffer].settings.fixendofline = value
+                }
+                ("trimtrailingwhitespace", Some(buffer)) => {
+                    ui.editor.buffers[buffer].settings.trim_trailing_whitespace = value
+                }
+                _ => ui.editor.emit_message(format!("Unknown option: {argument}")),
+            }
+        }
+    }
+}
+
+fn resolve_path(argument: &str) -> PathBuf {
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    let directory = std::env::current_dir().unwrap_or_default();
+    util::resolve_path(argument, home.as_deref(), &directory)
+}
+
+// Opens the file under the cursor, resolved relative to the directory of the current file.
+fn goto_file(ui: &mut UI) -> io::Result<()> {
+    let Some((path, directory)) = ui.editor.path_under_cursor()
+    else {
+        return Err(io::Error::other("No file name under cursor"));
+    };
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    let directory = directory.unwrap_or_else(|| std::env::current_dir().unwrap_or_default());
+    let resolved = util::resolve_path(&path, home.as_deref(), &directory);
+    if !resolved.is_file() {
+        return Err(io::Error::other(format!("Can't find file \"{path}\"")));
+    }
+    ui.editor.edit(resolved)
+}
+
+// Splits a `:g/pattern/command` command line into whether it applies to the lines that do not
+// match, with `:g!` or `:v`, the pattern, and the command. Any punctuation can delimit the pattern.
+fn parse_global(command_line: &str) -> Option<(bool, &str, &str)> {
+    let names = [
+        ("global!", true),
+        ("vglobal", true),
+        ("global", false),
+        ("g!", true),
+        ("v", true),
+        ("g", false),
+    ];
+    let (invert, rest) = names
+        .into_iter()
+        .find_map(|(name, invert)| Some((invert, command_line.strip_prefix(name)?)))?;
+    let pieces = split_delimited(rest, 2)?;
+    Some((invert, pieces[0], pieces.get(1).map_or("", |command| command.trim())))
+}
+
+// Splits a `:s/pattern/replacement/flags` command line into its pattern, replacement, and flags.
+fn parse_substitute(command_line: &str) -> Option<(&str, &str, &str)> {
+    let rest = (command_line.strip_prefix("substitute")).or(command_line.strip_prefix('s'))?;
+    let pieces = split_delimited(rest, 3)?;
+    let piece = |index: usize| pieces.get(index).copied().unwrap_or_default();
+    Some((piece(0), piece(1), piece(2).trim()))
+}
+
+// Splits the text into at most `count` pieces at the delimiter it starts with, which can be any
+// punctuation character.
+fn split_delimited(text: &str, count: usize) -> Option<Vec<&str>> {
+    let delimiter = text.chars().next().filter(char::is_ascii_punctuation)?;
+    Some(text[1..].splitn(count, delimiter).collect())
+}
+
+// Errors from a command are shown in the status line instead of ending the program.
+// A command line that starts with `'<,'>` applies to the lines of the last visual selection, and
+// one that starts with `%` applies to every line.
+fn execute_command_line(ui: &mut UI) {
+    let command_line = ui.command_line.clone();
+    let (lines, command_line) = match command_line.strip_prefix("'<,'>") {
+        Some(rest) => match ui.visual_lines.clone() {
+            Some(lines) => (Some(lines), rest),
+            None => {
+                ui.editor.emit_message(String::from("No visual selection"));
+                return;
+            }
+        },
+        None => match command_line.strip_prefix('%') {
+            Some(rest) => (Some(0..usize::MAX), rest),
+            None => (None, command_line.as_str()),
+        },
+    };
+    if let Some((invert, pattern, command)) = parse_global(command_line) {
         match command {
-            "e" | "edit" => {
-                if let Some(argument) = pieces.next() {
-                    ui.editor.edit(argument.into())?;
+            "d" | "delete" => ui.editor.delete_matching_lines(lines, pattern, invert),
+            _ => ui.editor.emit_message(format!("Unsupported command for :global: {command}")),
+        }
+        return;
+    }
+    if let Some((pattern, replacement, flags)) = parse_substitute(command_line) {
+        match flags {
+            "" | "g" => ui.editor.substitute(lines, pattern, replacement, flags == "g"),
+            _ => ui.editor.emit_message(format!("Invalid substitute flags: {flags}")),
+        }
+        return;
+    }
+    // A count before a command, like `:10split`, is separate from a line number on its own.
+    let count_end = command_line.find(|character: char| !character.is_ascii_digit()).unwrap_or(0);
+    let count = command_line[..count_end].parse().ok();
+    let mut pieces = command_line[count_end..].split_whitespace();
+    if let Some(command) = pieces.next() {
+        if let Err(error) = execute_command(ui, command, pieces, lines, count) {
+            ui.editor.emit_message(error.to_string());
+        }
+    }
+}
+
+fn execute_command<'a>(
+    ui: &mut UI,
+    command: &str,
+    mut pieces: impl Iterator<Item = &'a str>,
+    lines: Option<Range<usize>>,
+    count: Option<usize>,
+) -> io::Result<()> {
+    match command {
+        "e" | "edit" => {
+            if let Some(argument) = pieces.next() {
+                ui.editor.edit(resolve_path(argument))?;
+            }
+        }
+        "vie" | "view" => {
+            if let Some(argument) = pieces.next() {
+                ui.editor.edit(resolve_path(argument))?;
+                let view = ui.editor.windows[ui.editor.window_focus()].view;
+                if let Some(view) = view {
+                    ui.editor.buffers[view.buffer].settings.modifiable = false;
                 }
             }
-            "q" | "quit" => ui.quit = true,
-            "sp" | "split" => ui.editor.horizontal_split_window(),
-            "vsp" | "vsplit" => ui.editor.vertical_split_window(),
-            "redraw" => ui.editor.force_redraw(),
-            "tabopen" => ui.editor.tab_open(),
-            "tabclose" => ui.editor.tab_close(),
-            "tabnext" => ui.editor.tab_next(),
-            "tabprev" | "tabprevious" => ui.editor.tab_previous(),
-            _ => ui.editor.status = Some(format!("Unrecognized command: {command}")),
         }
+        "ene" | "enew" => ui.editor.edit_new(),
+        "new" => {
+            if let Some(id) = ui.editor.horizontal_split_window(window_length(count)) {
+                ui.editor.set_window_focus(id);
+                ui.editor.edit_new();
+            }
+        }
+        "w" | "write" | "w!" | "write!" => {
+            ui.editor.write_buffer(pieces.next().map(resolve_path), command.ends_with('!'))?;
+        }
+        "wq" | "wq!" | "x" | "xit" | "x!" | "xit!" => {
+            let force = command.ends_with('!');
+            let path = pieces.next().map(resolve_path);
+            // `:x` only writes when there are changes.
+            let view = ui.editor.windows[ui.editor.window_focus()].view;
+            let modified = view.is_some_and(|view| ui.editor.buffers[view.buffer].modified);
+            if command.starts_with('w') || modified || path.is_some() {
+                ui.editor.write_buffer(path, force)?;
+            }
+            if !force {
+                ui.editor.check_unsaved()?;
+            }
+            ui.quit = true;
+        }
+        "bd" | "bdelete" | "bd!" | "bdelete!" => {
+            let buffer = match pieces.next() {
+                Some(argument) => {
+                    let index =
+                        argument.parse().ok().and_then(|number: usize| number.checked_sub(1));
+                    match index.and_then(|index| ui.editor.buffers.index_at(index)) {
+                        Some(buffer) => Some(buffer),
+                        None => return Err(io::Error::other(format!("No buffer {argument}"))),
+                    }
+                }
+                None => None,
+            };
+            ui.editor.delete_buffer(buffer, command.ends_with('!'))?;
+        }
+        "rec" | "recover" => ui.editor.recover()?,
+        "f" | "file" => ui.editor.show_file_info(),
+        "h" | "help" => ui.editor.show_scratch("Help", HELP),
+        "Ex" | "Explore" => ui.editor.explore(resolve_path(pieces.next().unwrap_or(".")))?,
+        "reg" | "registers" | "di" | "display" => {
+            let list = register_list(&ui.editor);
+            ui.editor.show_scratch("Registers", &list);
+        }
+        "wa" | "wall" | "wa!" | "wall!" => ui.editor.write_all(command.ends_with('!'))?,
+        "wqa" | "wqall" | "xa" | "xall" | "wqa!" | "wqall!" | "xa!" | "xall!" => {
+            let force = command.ends_with('!');
+            ui.editor.write_all(force)?;
+            if !force {
+                ui.editor.check_unsaved()?;
+            }
+            ui.quit = true;
+        }
+        // Quitting closes every window, so `:qa` is the same as `:q`.
+        "q" | "quit" | "q!" | "quit!" | "qa" | "qall" | "qa!" | "qall!" => {
+            if !command.ends_with('!') {
+                ui.editor.check_unsaved()?;
+            }
+            ui.quit = true;
+        }
+        "sp" | "split" | "vs" | "vsp" | "vsplit" => {
+            let (vertical, length) = (command.starts_with('v'), window_length(count));
+            match pieces.next() {
+                Some(argument) => {
+                    ui.editor.split_and_edit(vertical, length, resolve_path(argument))?;
+                }
+                None if vertical => {
+                    ui.editor.vertical_split_window(length);
+                }
+                None => {
+                    ui.editor.horizontal_split_window(length);
+                }
+            }
+        }
+        "clo" | "close" => ui.editor.close_window(),
+        "on" | "only" => ui.editor.only_window(),
+        "set" => {
+            for argument in pieces {
+                set_option(ui, argument);
+            }
+        }
+        "redraw" => force_redraw(ui),
+        "TrimWhitespace" => ui.editor.trim_trailing_whitespace(),
+        "ea" | "earlier" | "lat" | "later" => {
+            match pieces.next().map(str::parse).unwrap_or(Ok(1)) {
+                Ok(count) => ui.editor.undo(count, command.starts_with('l')),
+                Err(_) => ui.editor.emit_message(String::from("Invalid count")),
+            }
+        }
+        "undol" | "undolist" => ui.editor.show_undo_list(),
+        "sor" | "sort" | "sor!" | "sort!" => {
+            let mut sort = editor::Sort { reverse: command.ends_with('!'), ..Default::default() };
+            for option in pieces.flat_map(str::chars) {
+                match option {
+                    'u' => sort.unique = true,
+                    'n' => sort.numeric = true,
+                    _ => return Err(io::Error::other(format!("Invalid sort option: {option}"))),
+                }
+            }
+            ui.editor.sort_lines(lines, sort);
+        }
+        "noh" | "nohlsearch" => {
+            ui.editor.search_highlight = false;
+            ui.editor.force_redraw();
+        }
+        "tabopen" => ui.editor.tab_open(),
+        "tabclose" => ui.editor.tab_close(),
+        "tabm" | "tabmove" => {
+            let current = ui.editor.current_tab;
+            let index = match pieces.next() {
+                None => Some(usize::MAX),
+                Some(argument) => tab_move_index(argument, current),
+            };
+            match index {
+                Some(index) => ui.editor.tab_move(index),
+                None => ui.editor.emit_message(String::from("Invalid tab position")),
+            }
+        }
+        "tabnext" => ui.editor.tab_next(),
+        "tabprev" | "tabprevious" => ui.editor.tab_previous(),
+        "$" => ui.editor.goto_line(usize::MAX),
+        // A line number goes to that line, and numbers past the end go to the last line.
+        _ if command.chars().all(|character| character.is_ascii_digit()) => {
+            let line = command.parse().unwrap_or(usize::MAX);
+            ui.editor.goto_line(line.saturating_sub(1));
+        }
+        _ => ui.editor.emit_message(format!("Unrecognized command: {command}")),
     }
     Ok(())
 }
 
+// Counts too large for a window are kept too large, so that they are rejected.
+fn window_length(count: Option<usize>) -> Option<u16> {
+    count.map(|count| u16::try_from(count).unwrap_or(u16::MAX))
+}
+
+// The index that `:tabmove` moves the current tab to. A number moves the tab after the given tab,
+// counted before the move, and `+n` or `-n` moves it by `n`, or by one without a number.
+fn tab_move_index(argument: &str, current: usize) -> Option<usize> {
+    let parse = |offset: &str| if offset.is_empty() { Some(1) } else { offset.parse().ok() };
+    if let Some(offset) = argument.strip_prefix('+') {
+        parse(offset).map(|n| current.saturating_add(n))
+    }
+    else if let Some(offset) = argument.strip_prefix('-') {
+        parse(offset).map(|n| current.saturating_sub(n))
+    }
+    else {
+        argument.parse().ok().map(|n: usize| n - usize::from(n > current))
+    }
+}
+
+fn operator(prefix: char) -> editor::Operator {
+    match prefix {
+        'd' => editor::Operator::Delete,
+        'c' => editor::Operator::Change,
+        _ => editor::Operator::Yank,
+    }
+}
+
+fn handle_prefixed_key(ui: &mut UI, prefix: char, count: Option<usize>, character: char) {
+    match (prefix, character) {
+        ('>', '>') => ui.editor.shift_lines(count.unwrap_or(1), true),
+        ('<', '<') => ui.editor.shift_lines(count.unwrap_or(1), false),
+        ('g', 'g') => ui.editor.goto_line(count.unwrap_or(1).saturating_sub(1)),
+        ('g', 'f') => {
+            if let Err(error) = goto_file(ui) {
+                ui.editor.emit_message(error.to_string());
+            }
+        }
+        ('g', 't') => match count {
+            Some(count) => ui.editor.goto_tab(count - 1),
+            None => ui.editor.tab_next(),
+        },
+        ('g', 'T') => {
+            for _ in 0..count.unwrap_or(1) {
+                ui.editor.tab_previous();
+            }
+        }
+        ('d', 'd') => ui.editor.delete_lines(count.unwrap_or(1)),
+        ('y', 'y') => ui.editor.yank_lines(count.unwrap_or(1)),
+        ('"', name @ ('a'..='z' | 'A'..='Z' | '"')) => {
+            (ui.count, ui.register) = (count, Some(name));
+        }
+        ('c', 'c') => ui.editor.change_lines(count.unwrap_or(1)),
+        ('d' | 'c' | 'y', scope @ ('i' | 'a')) => {
+            ui.pending_object = Some((operator(prefix), scope == 'a'));
+            ui.register = Some(ui.editor.register);
+        }
+        ('d' | 'c' | 'y', motion @ ('w' | '$')) => {
+            ui.editor.apply_to_motion(operator(prefix), motion, count.unwrap_or(1));
+        }
+        ('r', character) => ui.editor.replace_chars(character, count.unwrap_or(1)),
+        ('m', name @ 'a'..='z') => ui.editor.set_mark(name),
+        ('`', name @ 'a'..='z') => ui.editor.jump_to_mark(name),
+        ('f' | 'F' | 't' | 'T', character) => {
+            let find = editor::FindChar {
+                character,
+                backward: prefix.is_uppercase(),
+                till: prefix.eq_ignore_ascii_case(&'t'),
+            };
+            ui.editor.find_char(find, count.unwrap_or(1));
+        }
+        _ => {}
+    }
+}
+
 fn handle_key(ui: &mut UI, key: KeyEvent) -> io::Result<()> {
     if key.kind != KeyEventKind::Press {
         return Ok(());
     }
+    ui.editor.clear_shown_message();
+    if key.code != KeyCode::Tab {
+        ui.completion = None;
+    }
+    // A pending count, prefix, or register only applies to the key that immediately follows it.
+    let (count, prefix, register) = (ui.count.take(), ui.prefix.take(), ui.register.take());
+    ui.editor.register = register.unwrap_or('"');
+
+    if let Some((operator, around)) = ui.pending_object.take() {
+        if let KeyCode::Char(object) = key.code {
+            ui.editor.apply_to_text_object(operator, object, around);
+        }
+        return Ok(());
+    }
+
+    // Enter after `r` replaces with a line break.
+    let prefixed = match key.code {
+        KeyCode::Char(character) => Some(character),
+        KeyCode::Enter if prefix == Some('r') => Some('\n'),
+        _ => None,
+    };
+    if let (editor::Mode::Normal, Some(prefix), Some(character)) =
+        (ui.editor.mode, prefix, prefixed)
+    {
+        handle_prefixed_key(ui, prefix, count, character);
+        return Ok(());
+    }
 
     match ui.editor.mode {
         editor::Mode::Normal => match key.code {
+            KeyCode::Char(digit @ '0'..='9') if digit != '0' || count.is_some() => {
+                let digit = digit.to_digit(10).unwrap() as usize;
+                ui.count = Some(count.unwrap_or(0).saturating_mul(10).saturating_add(digit));
+                ui.register = register;
+            }
+            KeyCode::Char(
+                prefix @ ('g' | 'd' | 'c' | 'y' | 'm' | '`' | 'f' | 'F' | 't' | 'T' | '>' | '<'
+                | '"' | 'r'),
+            ) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+                (ui.count, ui.prefix, ui.register) = (count, Some(prefix), register);
+            }
+            // Like `:q`, quitting is refused while a buffer has unsaved changes.
             KeyCode::Char('c') if key.modifiers == KeyModifiers::CONTROL => {
-                ui.quit = true;
+                match ui.editor.check_unsaved() {
+                    Ok(()) => ui.quit = true,
+                    Err(error) => ui.editor.emit_message(error.to_string()),
+                }
             }
             KeyCode::Char('w') if key.modifiers == KeyModifiers::CONTROL => {
                 ui.editor.mode = editor::Mode::Window;
+                ui.count = count;
+            }
+            KeyCode::Char('g') if key.modifiers == KeyModifiers::CONTROL => {
+                ui.editor.show_file_info();
+            }
+            KeyCode::Enter => {
+                if let Err(error) = ui.editor.open_directory_entry() {
+                    ui.editor.emit_message(error.to_string());
+                }
             }
             KeyCode::Char('q') if key.modifiers == KeyModifiers::CONTROL => {
                 ui.editor.tab_close();
@@ -180,6 +1110,20 @@ fn handle_key(ui: &mut UI, key: KeyEvent) -> io::Result<()> {
             KeyCode::Char('t') if key.modifiers == KeyModifiers::CONTROL => {
                 ui.editor.tab_open();
             }
+            KeyCode::Char('o') if key.modifiers == KeyModifiers::CONTROL => {
+                ui.editor.jump_back();
+            }
+            KeyCode::Char('v') if key.modifiers == KeyModifiers::CONTROL => {
+                ui.editor.begin_visual_block();
+            }
+            KeyCode::Char('r') if key.modifiers == KeyModifiers::CONTROL => {
+                ui.editor.undo(count.unwrap_or(1), true);
+            }
+            // Terminals send `Ctrl-i` as a tab.
+            KeyCode::Tab => ui.editor.jump_forward(),
+            KeyCode::Char('i') if key.modifiers == KeyModifiers::CONTROL => {
+                ui.editor.jump_forward();
+            }
             KeyCode::Char(character) => match character {
                 'h' => ui.editor.move_cursor(Direction::Left),
                 'j' => ui.editor.move_cursor(Direction::Down),
@@ -187,68 +1131,224 @@ fn handle_key(ui: &mut UI, key: KeyEvent) -> io::Result<()> {
                 'l' => ui.editor.move_cursor(Direction::Right),
                 'H' => ui.editor.tab_previous(),
                 'L' => ui.editor.tab_next(),
-                'i' => ui.editor.mode = editor::Mode::Insert,
-                ':' => ui.editor.mode = editor::Mode::CommandLine,
+                'i' => ui.editor.begin_insert(),
+                'a' | 'A' | 'I' => ui.editor.begin_insert_at(character),
+                'x' => ui.editor.delete_chars(count.unwrap_or(1)),
+                '~' => ui.editor.toggle_case(count.unwrap_or(1)),
+                'D' => ui.editor.apply_to_motion(editor::Operator::Delete, '$', 1),
+                'C' => ui.editor.apply_to_motion(editor::Operator::Change, '$', 1),
+                'J' => ui.editor.join_lines(count.unwrap_or(1)),
+                '.' => ui.editor.repeat_change(count),
+                'R' => ui.editor.begin_replace(),
+                'u' => ui.editor.undo(count.unwrap_or(1), false),
+                'p' | 'P' => ui.editor.paste(count.unwrap_or(1), character == 'P'),
+                ':' => {
+                    ui.command_prefix = character;
+                    ui.editor.mode = editor::Mode::CommandLine;
+                }
+                '/' | '?' => {
+                    ui.command_prefix = character;
+                    ui.editor.mode = editor::Mode::CommandLine;
+                    ui.editor.begin_search_preview();
+                }
+                'n' => ui.editor.search_next(false),
+                'N' => ui.editor.search_next(true),
+                '*' => ui.editor.search_word(false),
+                '#' => ui.editor.search_word(true),
+                '%' => ui.editor.match_bracket(),
+                ';' => ui.editor.repeat_find_char(false, count.unwrap_or(1)),
+                ',' => ui.editor.repeat_find_char(true, count.unwrap_or(1)),
+                'G' => ui.editor.goto_line(count.map_or(usize::MAX, |count| count - 1)),
+                'q' => ui.editor.close_scratch(),
                 _ => {}
             },
             _ => {}
         },
         editor::Mode::Window => match key.code {
             KeyCode::Esc => ui.editor.mode = editor::Mode::Normal,
+            KeyCode::Char(digit @ '0'..='9') if digit != '0' || count.is_some() => {
+                let digit = digit.to_digit(10).unwrap() as usize;
+                ui.count = Some(count.unwrap_or(0).saturating_mul(10).saturating_add(digit));
+            }
             KeyCode::Char(character) => match character {
                 'h' => ui.editor.move_focus(Direction::Left),
                 'j' => ui.editor.move_focus(Direction::Down),
                 'k' => ui.editor.move_focus(Direction::Up),
                 'l' => ui.editor.move_focus(Direction::Right),
-                's' => ui.editor.horizontal_split_window(),
-                'v' => ui.editor.vertical_split_window(),
+                'H' => ui.editor.move_window_to_edge(Direction::Left),
+                'J' => ui.editor.move_window_to_edge(Direction::Down),
+                'K' => ui.editor.move_window_to_edge(Direction::Up),
+                'L' => ui.editor.move_window_to_edge(Direction::Right),
+                '+' => ui.editor.resize_window(false, 1),
+                '-' => ui.editor.resize_window(false, -1),
+                '>' => ui.editor.resize_window(true, 1),
+                '<' => ui.editor.resize_window(true, -1),
+                's' => {
+                    ui.editor.horizontal_split_window(window_length(count));
+                }
+                'v' => {
+                    ui.editor.vertical_split_window(window_length(count));
+                }
+                'q' | 'c' => ui.editor.close_window(),
+                '=' => ui.editor.balance_windows(),
+                'x' => ui.editor.swap_window(),
+                'o' => ui.editor.only_window(),
                 'w' => ui.editor.rotate_focus_forward(),
                 'W' => ui.editor.rotate_focus_backward(),
                 _ => {}
             },
             _ => {}
         },
+        editor::Mode::VisualBlock => match key.code {
+            KeyCode::Esc => ui.editor.set_mode(editor::Mode::Normal),
+            KeyCode::Char('c' | 'v') if key.modifiers == KeyModifiers::CONTROL => {
+                ui.editor.set_mode(editor::Mode::Normal);
+            }
+            KeyCode::Char(character) => match character {
+                'h' => ui.editor.move_cursor(Direction::Left),
+                'j' => ui.editor.move_cursor(Direction::Down),
+                'k' => ui.editor.move_cursor(Direction::Up),
+                'l' => ui.editor.move_cursor(Direction::Right),
+                'd' | 'x' => ui.editor.delete_block(),
+                '>' => ui.editor.shift_block(true),
+                '<' => ui.editor.shift_block(false),
+                ':' => {
+                    ui.visual_lines = ui.editor.visual_block().map(|(_, lines, _)| lines);
+                    ui.editor.set_mode(editor::Mode::CommandLine);
+                    ui.command_prefix = character;
+                    set_command_line(ui, String::from("'<,'>"));
+                }
+                'I' => ui.editor.begin_block_insert(false),
+                'A' => ui.editor.begin_block_insert(true),
+                _ => {}
+            },
+            _ => {}
+        },
         editor::Mode::Insert => match key.code {
-            KeyCode::Esc => ui.editor.mode = editor::Mode::Normal,
-            KeyCode::Char(character) => ui.editor.status = Some(format!("got '{character}'")),
+            KeyCode::Esc => ui.editor.set_mode(editor::Mode::Normal),
+            KeyCode::Char(character) => ui.editor.insert_char(character),
+            KeyCode::Enter => ui.editor.insert_newline(),
+            KeyCode::Tab => ui.editor.insert_tab(),
+            KeyCode::Backspace => ui.editor.delete_char_backward(),
+            _ => {}
+        },
+        editor::Mode::Replace => match key.code {
+            KeyCode::Esc => ui.editor.set_mode(editor::Mode::Normal),
+            KeyCode::Char(character) => ui.editor.overwrite_char(character),
+            KeyCode::Enter => ui.editor.overwrite_char('\n'),
+            KeyCode::Backspace => ui.editor.restore_overwritten_char(),
             _ => {}
         },
         editor::Mode::CommandLine => match key.code {
-            KeyCode::Esc => ui.editor.mode = editor::Mode::Normal,
+            KeyCode::Tab if ui.command_prefix == ':' => {
+                match &mut ui.completion {
+                    Some(completion) => completion.advance(),
+                    None => {
+                        ui.completion = Completion::new(&ui.command_line, COMMANDS, PATH_COMMANDS)
+                    }
+                }
+                if let Some(completion) = &ui.completion {
+                    set_command_line(ui, completion.current());
+                }
+            }
+            KeyCode::Esc => {
+                ui.editor.end_search_preview();
+                command_history(ui).reset();
+                set_command_line(ui, String::new());
+                ui.editor.mode = editor::Mode::Normal;
+            }
             KeyCode::Char('c') if key.modifiers == KeyModifiers::CONTROL => {
+                command_history(ui).reset();
                 if ui.command_line.is_empty() {
+                    ui.editor.end_search_preview();
                     ui.editor.mode = editor::Mode::Normal;
                 }
                 else {
-                    ui.command_line.clear();
+                    set_command_line(ui, String::new());
                 }
             }
+            KeyCode::Up => {
+                let draft = ui.command_line.clone();
+                if let Some(entry) = command_history(ui).previous(&draft).map(str::to_owned) {
+                    set_command_line(ui, entry);
+                }
+            }
+            KeyCode::Down => {
+                if let Some(entry) = command_history(ui).next().map(str::to_owned) {
+                    set_command_line(ui, entry);
+                }
+            }
+            KeyCode::Left => {
+                if let Some(character) = ui.command_line[..ui.command_cursor].chars().next_back() {
+                    ui.command_cursor -= character.len_utf8();
+                }
+            }
+            KeyCode::Right => {
+                if let Some(character) = ui.command_line[ui.command_cursor..].chars().next() {
+                    ui.command_cursor += character.len_utf8();
+                }
+            }
+            KeyCode::Home => ui.command_cursor = 0,
+            KeyCode::End => ui.command_cursor = ui.command_line.len(),
             KeyCode::Char(character) => {
-                ui.command_line.push(character);
+                ui.command_line.insert(ui.command_cursor, character);
+                ui.command_cursor += character.len_utf8();
             }
             KeyCode::Backspace => {
-                ui.command_line.pop();
+                if let Some(character) = ui.command_line[..ui.command_cursor].chars().next_back() {
+                    ui.command_cursor -= character.len_utf8();
+                    ui.command_line.remove(ui.command_cursor);
+                }
+            }
+            KeyCode::Delete if ui.command_cursor < ui.command_line.len() => {
+                ui.command_line.remove(ui.command_cursor);
             }
             KeyCode::Enter => {
-                execute_command_line(ui)?;
-                ui.command_line.clear();
+                match ui.command_prefix {
+                    ':' => execute_command_line(ui),
+                    prefix => {
+                        ui.editor.end_search_preview();
+                        ui.editor.search(ui.command_line.clone(), prefix == '?');
+                    }
+                }
+                let command_line = std::mem::take(&mut ui.command_line);
+                command_history(ui).push(command_line);
+                ui.command_cursor = 0;
                 ui.editor.mode = editor::Mode::Normal;
             }
             _ => {}
         },
     }
 
+    if ui.editor.mode == editor::Mode::CommandLine && ui.command_prefix != ':' {
+        ui.editor.preview_search(&ui.command_line, ui.command_prefix == '?');
+    }
     Ok(())
 }
 
+fn handle_mouse(ui: &mut UI, event: MouseEvent) {
+    let position = Position { x: event.column, y: event.row };
+    match event.kind {
+        MouseEventKind::Down(MouseButton::Left) if ui.editor.mode != editor::Mode::CommandLine => {
+            ui.editor.click(position);
+        }
+        MouseEventKind::ScrollUp => ui.editor.scroll_window_at(position, Direction::Up),
+        MouseEventKind::ScrollDown => ui.editor.scroll_window_at(position, Direction::Down),
+        _ => {}
+    }
+}
+
 fn handle_event(ui: &mut UI, event: Event) -> io::Result<()> {
     match event {
         Event::Key(event) => {
             handle_key(ui, event)?;
         }
+        Event::Mouse(event) => handle_mouse(ui, event),
         Event::Resize(width, height) => {
-            ui.editor.size = terminal::Size { width, height };
+            ui.editor.resize(terminal::Size { width, height });
+            force_redraw(ui);
         }
+        Event::FocusGained if ui.editor.settings.autoread => ui.editor.reload_changed_files(),
         _ => {}
     }
     Ok(())
@@ -259,6 +1359,22 @@ impl UI {
         UI {
             editor: editor::Editor::new(size),
             command_line: String::new(),
+            command_cursor: 0,
+            command_history: History::new(COMMAND_HISTORY_CAPACITY),
+            search_history: History::new(COMMAND_HISTORY_CAPACITY),
+            command_prefix: ':',
+            completion: None,
+            count: None,
+            prefix: None,
+            pending_object: None,
+            register: None,
+            visual_lines: None,
+            last_status_line: String::new(),
+            status_overlay: false,
+            cursor_line: None,
+            visual_block: None,
+            frame: Vec::new(),
+            clear: true,
             quit: false,
         }
     }
@@ -266,8 +1382,276 @@ impl UI {
     pub fn run(&mut self) -> io::Result<()> {
         while !self.quit {
             draw(self)?;
-            handle_event(self, event::read()?)?;
+            // Without periodic work to do, the loop sleeps until the next event.
+            let timeout = self.editor.time_until_tick(Instant::now());
+            if timeout.map_or(Ok(true), event::poll)? {
+                handle_event(self, event::read()?)?;
+            }
+            self.editor.tick(Instant::now());
         }
+        self.editor.remove_swap_files();
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::editor;
+    use crate::terminal::{Position, Size};
+    use std::io::{self, Write};
+
+    struct WriteCounter(usize);
+
+    impl Write for WriteCounter {
+        fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+            self.0 += 1;
+            Ok(buffer.len())
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // A UI whose focused window views a buffer with the text.
+    fn ui_with_text(size: Size, text: &str) -> super::UI {
+        let mut ui = super::UI::new(size);
+        let buffer = editor::Buffer { text: String::from(text).into(), ..Default::default() };
+        let buffer = ui.editor.buffers.push(buffer);
+        let id = ui.editor.window_focus();
+        ui.editor.windows[id].view = Some(editor::View { buffer });
+        ui
+    }
+
+    // The output of drawing the focused window.
+    fn frame(ui: &super::UI, focus: bool) -> String {
+        let mut frame = Vec::new();
+        let window = &ui.editor.windows[ui.editor.window_focus()];
+        super::draw_window(ui, &mut frame, window, focus).unwrap();
+        String::from_utf8(frame).unwrap()
+    }
+
+    // Run with `cargo test --release -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn bench_frame_batching() {
+        let text = vec!["x".repeat(80); 40].join("\n");
+        let ui = ui_with_text(Size { width: 80, height: 41 }, &text);
+        let window = ui.editor.windows[ui.editor.window_focus()];
+
+        // Writing straight to a line buffered writer is what printing to stdout does.
+        let mut unbatched = io::LineWriter::new(WriteCounter(0));
+        let start = std::time::Instant::now();
+        for _ in 0..100 {
+            super::draw_window(&ui, &mut unbatched, &window, true).unwrap();
+            unbatched.flush().unwrap();
+        }
+        let unbatched_time = start.elapsed();
+
+        let mut batched = WriteCounter(0);
+        let mut frame = Vec::new();
+        let start = std::time::Instant::now();
+        for _ in 0..100 {
+            frame.clear();
+            super::draw_window(&ui, &mut frame, &window, true).unwrap();
+            batched.write_all(&frame).unwrap();
+        }
+        let batched_time = start.elapsed();
+
+        let unbatched_writes = unbatched.get_ref().0 / 100;
+        eprintln!("80x40 frame: unbatched {unbatched_writes} writes, {unbatched_time:?} per 100");
+        eprintln!("80x40 frame: batched {} writes, {batched_time:?} per 100", batched.0 / 100);
+        assert!(batched.0 < unbatched.get_ref().0);
+    }
+
+    #[test]
+    fn draw_narrow_window() {
+        let ui = super::UI::new(Size { width: 80, height: 24 });
+        for width in 0..3 {
+            let window = editor::Window {
+                size: Size { width, height: 3 },
+                ..ui.editor.windows[ui.editor.window_focus()]
+            };
+            let mut frame = Vec::new();
+            super::draw_window(&ui, &mut frame, &window, true).unwrap();
+            let frame = String::from_utf8(frame).unwrap();
+            let borders = ['┌', '┐', '│', '└', '┘'].map(|border| frame.matches(border).count());
+            let expected = match width {
+                0 => [0, 0, 0, 0, 0],
+                1 => [1, 0, 1, 1, 0],
+                _ => [1, 1, 2, 1, 1],
+            };
+            assert_eq!(borders, expected);
+        }
+    }
+
+    #[test]
+    fn draw_divider() {
+        let ui = super::UI::new(Size { width: 80, height: 24 });
+        let window = ui.editor.windows[ui.editor.window_focus()];
+        let borders = |divider, x, width| {
+            let (position, size) = (window.position.offset_x(x), Size { width, height: 4 });
+            let mut window = editor::Window { position, size, ..window };
+            window.settings.divider = divider;
+            let mut frame = Vec::new();
+            super::draw_window(&ui, &mut frame, &window, true).unwrap();
+            let frame = String::from_utf8(frame).unwrap();
+            ['┌', '┐', '│', '└', '┘', '─'].map(|border| frame.matches(border).count())
+        };
+        assert_eq!(borders(true, 0, 40), [1, 0, 4, 1, 0, 78]);
+        assert_eq!(borders(true, 40, 40), [0, 1, 2, 0, 1, 78]);
+        assert_eq!(borders(true, 20, 20), [0, 0, 2, 0, 0, 40]);
+        assert_eq!(borders(false, 20, 20), [1, 1, 4, 1, 1, 36]);
+    }
+
+    #[test]
+    fn draw_inactive_cursor() {
+        let mut ui = ui_with_text(Size { width: 80, height: 24 }, "a\tb\nc");
+        let id = ui.editor.window_focus();
+        let marker = "\x1b[48;5;242m";
+        assert!(!frame(&ui, true).contains(marker));
+        assert!(frame(&ui, false).contains(&format!("{marker}a")));
+        ui.editor.windows[id].cursor.x = 1;
+        assert!(frame(&ui, false).contains(&format!("{marker} ")));
+        ui.editor.windows[id].cursor = Position { x: 1, y: 1 };
+        assert!(frame(&ui, false).contains(&format!("{marker} ")));
+        ui.editor.windows[id].scroll_y = 2;
+        assert!(!frame(&ui, false).contains(marker));
+    }
+
+    #[test]
+    fn draw_color_column() {
+        let mut ui = ui_with_text(Size { width: 80, height: 24 }, "abcd\nx");
+        let id = ui.editor.window_focus();
+        let marker = "\x1b[48;5;235m";
+        assert!(!frame(&ui, true).contains(marker));
+        ui.editor.windows[id].settings.colorcolumn = 3;
+        assert_eq!(frame(&ui, true).matches(marker).count(), 2);
+        assert!(frame(&ui, true).contains(&format!("\x1b[2;6H{marker}c")));
+        assert!(frame(&ui, true).contains(&format!("\x1b[3;6H{marker} ")));
+        ui.editor.windows[id].scroll_x = 1;
+        assert!(frame(&ui, true).contains(&format!("\x1b[2;5H{marker}c")));
+        ui.editor.windows[id].scroll_x = 3;
+        assert!(!frame(&ui, true).contains(marker));
+    }
+
+    #[test]
+    fn line_view() {
+        let cells = |line, offset, width| -> String {
+            super::line_view(line, offset, width, 4, false)
+                .map(|(_, character)| character)
+                .collect()
+        };
+        assert_eq!(cells("a\tb\tc", 0, 80), "a   b   c");
+        assert_eq!(cells("\tab", 2, 3), "  a");
+        assert_eq!(cells("äöü", 1, 1), "ö");
+        let indices: Vec<usize> =
+            super::line_view("ä\tb", 1, 3, 4, false).map(|(i, _)| i).collect();
+        assert_eq!(indices, [2, 2, 2]);
+    }
+
+    #[test]
+    fn line_view_list() {
+        let cells = |line, offset| -> String {
+            super::line_view(line, offset, 80, 4, true).map(|(_, character)| character).collect()
+        };
+        assert_eq!(cells("a\tb c \t ", 0), "a→  b c·→   ·¶");
+        assert_eq!(cells("\tx", 2), "  x¶");
+        assert_eq!(cells("", 0), "¶");
+        let indices: Vec<usize> = super::line_view("a ", 0, 80, 4, true).map(|(i, _)| i).collect();
+        assert_eq!(indices, [0, 1, 2]);
+    }
+
+    #[test]
+    fn ruler_position() {
+        assert_eq!(super::ruler_position(0, 0), "All");
+        assert_eq!(super::ruler_position(0, 1), "All");
+        assert_eq!(super::ruler_position(0, 10), "Top");
+        assert_eq!(super::ruler_position(4, 10), "50%");
+        assert_eq!(super::ruler_position(9, 10), "Bot");
+        assert_eq!(super::ruler_position(1, 3), "66%");
+    }
+
+    #[test]
+    fn register_list() {
+        let mut editor = editor::Editor::new(Size { width: 80, height: 24 });
+        assert_eq!(super::register_list(&editor), "Name  Type  Content");
+        let register = |text: &str, linewise| editor::Register { text: text.to_owned(), linewise };
+        editor.registers.insert('a', register(&"x".repeat(70), false));
+        editor.registers.insert('"', register("one\n\ttwo", true));
+        assert_eq!(
+            super::register_list(&editor),
+            format!(
+                "Name  Type  Content\n\"\"    l     one^J^Itwo\n\"a    c     {}…",
+                "x".repeat(59)
+            )
+        );
+    }
+
+    #[test]
+    fn parse_global() {
+        assert_eq!(super::parse_global("g/a b/d"), Some((false, "a b", "d")));
+        assert_eq!(super::parse_global("g!/a/ delete"), Some((true, "a", "delete")));
+        assert_eq!(super::parse_global("v#a/b#d"), Some((true, "a/b", "d")));
+        assert_eq!(super::parse_global("global/a"), Some((false, "a", "")));
+        assert_eq!(super::parse_global("vglobal/a/d"), Some((true, "a", "d")));
+        assert_eq!(super::parse_global("vsplit"), None);
+        assert_eq!(super::parse_global("goto"), None);
+    }
+
+    #[test]
+    fn parse_substitute() {
+        assert_eq!(super::parse_substitute("s/a b/c/g"), Some(("a b", "c", "g")));
+        assert_eq!(super::parse_substitute("substitute#a#b"), Some(("a", "b", "")));
+        assert_eq!(super::parse_substitute("s/a"), Some(("a", "", "")));
+        assert_eq!(super::parse_substitute("s/a/b/c/d"), Some(("a", "b", "c/d")));
+        assert_eq!(super::parse_substitute("sort"), None);
+        assert_eq!(super::parse_substitute("set"), None);
+    }
+
+    #[test]
+    fn truncate_end() {
+        assert_eq!(super::truncate_end("main.rs", 7), "main.rs");
+        assert_eq!(super::truncate_end("main.rs", 5), "main…");
+        assert_eq!(super::truncate_end("main.rs", 1), "…");
+        assert_eq!(super::truncate_end("main.rs", 0), "");
+    }
+
+    #[test]
+    fn status_line_width() {
+        let mut ui = ui_with_text(Size { width: 20, height: 24 }, "a\nb\nc");
+        let id = ui.editor.window_focus();
+        let line = super::status_line(&ui);
+        assert_eq!(line, "-- Normal -- [N… Top");
+        ui.editor.emit_message(String::from("a long message that does not fit"));
+        ui.editor.windows[id].cursor.y = 2;
+        assert_eq!(super::status_line(&ui), "-- Normal -- a … Bot");
+        for width in 0..30 {
+            ui.editor.size.width = width;
+            assert!(super::status_line(&ui).chars().count() <= width as usize);
+            ui.editor.mode = editor::Mode::CommandLine;
+            assert!(super::status_line(&ui).chars().count() <= width as usize);
+            ui.editor.mode = editor::Mode::Normal;
+        }
+    }
+
+    #[test]
+    fn tab_move_index() {
+        assert_eq!(super::tab_move_index("+2", 1), Some(3));
+        assert_eq!(super::tab_move_index("+", 1), Some(2));
+        assert_eq!(super::tab_move_index("-", 1), Some(0));
+        assert_eq!(super::tab_move_index("-5", 1), Some(0));
+        assert_eq!(super::tab_move_index("0", 1), Some(0));
+        assert_eq!(super::tab_move_index("3", 1), Some(2));
+        assert_eq!(super::tab_move_index("+x", 1), None);
+        assert_eq!(super::tab_move_index("é", 1), None);
+    }
+
+    #[test]
+    fn truncate_start() {
+        assert_eq!(super::truncate_start("main.rs", 10), "main.rs");
+        assert_eq!(super::truncate_start("main.rs", 7), "main.rs");
+        assert_eq!(super::truncate_start("main.rs", 5), "<n.rs");
+        assert_eq!(super::truncate_start("main.rs", 1), "<");
+        assert_eq!(super::truncate_start("main.rs", 0), "");
+    }
+}
@@ -1,3 +1,5 @@
+use std::path::{Path, PathBuf};
+
 #[derive(Clone, Copy, Debug)]
 pub enum Direction {
     Up,
@@ -19,6 +21,32 @@ pub fn rotate_backward(min: usize, max: usize, n: usize) -> usize {
     (if n == min { max } else { n }) - 1
 }
 
+// Maps a coordinate from `0..=old` to `0..=new`, keeping both ends fixed.
+pub fn rescale(n: u16, old: u16, new: u16) -> u16 {
+    match n {
+        0 => 0,
+        n if n == old => new,
+        n => (n as u32 * new as u32 / old as u32) as u16,
+    }
+}
+
+// The screen column a tab at `column` extends to.
+pub fn next_tab_stop(column: usize, tabstop: u16) -> usize {
+    let tabstop = tabstop.max(1) as usize;
+    column + tabstop - column % tabstop
+}
+
+// Expands a leading `~` to `home` and makes relative paths absolute against `directory`.
+pub fn resolve_path(path: &str, home: Option<&Path>, directory: &Path) -> PathBuf {
+    let path = match (path.strip_prefix('~'), home) {
+        (Some(rest), Some(home)) if rest.is_empty() || rest.starts_with('/') => {
+            home.join(rest.trim_start_matches('/'))
+        }
+        _ => PathBuf::from(path),
+    };
+    directory.join(path)
+}
+
 #[cfg(test)]
 mod tests {
     #[test]
@@ -33,4 +61,31 @@ mod tests {
         assert_eq!(super::rotate_backward(0, 3, 1), 0);
         assert_eq!(super::rotate_backward(0, 3, 2), 1);
     }
+    #[test]
+    fn rescale() {
+        assert_eq!(super::rescale(0, 80, 40), 0);
+        assert_eq!(super::rescale(80, 80, 40), 40);
+        assert_eq!(super::rescale(40, 80, 40), 20);
+        assert_eq!(super::rescale(41, 80, 120), 61);
+        assert_eq!(super::rescale(0, 0, 40), 0);
+        assert_eq!(super::rescale(10, 10, 0), 0);
+    }
+    #[test]
+    fn next_tab_stop() {
+        assert_eq!(super::next_tab_stop(0, 4), 4);
+        assert_eq!(super::next_tab_stop(3, 4), 4);
+        assert_eq!(super::next_tab_stop(4, 4), 8);
+        assert_eq!(super::next_tab_stop(5, 0), 6);
+    }
+    #[test]
+    fn resolve_path() {
+        use std::path::Path;
+        let resolve = |path| super::resolve_path(path, Some(Path::new("/home/u")), Path::new("/w"));
+        assert_eq!(resolve("~/notes.txt"), Path::new("/home/u/notes.txt"));
+        assert_eq!(resolve("~"), Path::new("/home/u"));
+        assert_eq!(resolve("~user/a"), Path::new("/w/~user/a"));
+        assert_eq!(resolve("src/a.rs"), Path::new("/w/src/a.rs"));
+        assert_eq!(resolve("/etc/hosts"), Path::new("/etc/hosts"));
+        assert_eq!(super::resolve_path("~/a", None, Path::new("/w")), Path::new("/w/~/a"));
+    }
 }